@@ -13,10 +13,13 @@
   outputs = {mill-scale, ...}:
     mill-scale ./. {
       cargoTest = false;
-      extraPaths = [./data];
+      extraPaths = [./data ./schema];
       withOverlays = [(import ./nix/overlay.nix)];
       checks = {
-        test = pkgs: pkgs.nixosTest (import ./nix/test.nix);
+        test = pkgs: pkgs.nixosTest (import ./nix/test.nix {testArgs = "--suite smoke";});
+      };
+      perSystem = pkgs: {
+        test-full = pkgs.nixosTest (import ./nix/test.nix {timeout = 1800;});
       };
     };
 }
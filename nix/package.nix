@@ -6,7 +6,7 @@
 }: let
   inherit (lib.sources) sourceByRegex;
   inherit (builtins) fromTOML readFile;
-  src = sourceByRegex ../. ["Cargo.*" "(src|data)(/.*)?"];
+  src = sourceByRegex ../. ["Cargo.*" "(src|data|schema)(/.*)?"];
   cargoPackage = (fromTOML (readFile ../Cargo.toml)).package;
 in
   rustPlatform.buildRustPackage {
@@ -1,4 +1,7 @@
 {
+  testArgs ? "",
+  timeout ? 180,
+}: {
   pkgs,
   lib,
   ...
@@ -57,6 +60,10 @@
             };
             "/static/" = {
               alias = "/demos/";
+              extraConfig = ''
+                default_type application/octet-stream;
+                add_header Content-Disposition attachment;
+              '';
             };
           };
         };
@@ -123,6 +130,8 @@
         BASE_URL='http://localhost/'\
         ACCESS_KEY='access'\
         EDIT_KEY='edit'\
-        ${testBinary}", timeout=180)
+        DEMO_ROOT='/demos'\
+        STATIC_URL='http://localhost/static'\
+        ${testBinary} ${testArgs}", timeout=${toString timeout})
   '';
 }
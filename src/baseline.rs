@@ -0,0 +1,140 @@
+//! Latency baselines
+//!
+//! The duration of every successful step is recorded during the run. When `LATENCY_BASELINE` is
+//! set, the recorded latencies are written to the baseline file if it doesn't exist yet, or
+//! compared against it otherwise, reporting any step that became significantly slower.
+
+use crate::env::env_or;
+use crate::harness::Harness;
+use color_eyre::{eyre::WrapErr, Result};
+use colored::Colorize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Step durations by `<test>/<step>`, shared between all tests of a run
+#[derive(Clone, Default)]
+pub struct Timings(Arc<Mutex<BTreeMap<String, Vec<Duration>>>>);
+
+impl Timings {
+    pub fn record(&self, test: &str, step: &str, duration: Duration) {
+        self.0
+            .lock()
+            .unwrap()
+            .entry(format!("{}/{}", test, step))
+            .or_default()
+            .push(duration);
+    }
+
+    /// Median duration of every step in milliseconds
+    fn medians(&self) -> BTreeMap<String, f64> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(step, durations)| {
+                let mut durations = durations.clone();
+                durations.sort_unstable();
+                (
+                    step.clone(),
+                    durations[durations.len() / 2].as_secs_f64() * 1000.0,
+                )
+            })
+            .collect()
+    }
+}
+
+struct BaselineConfig {
+    path: PathBuf,
+    /// Overwrite the existing baseline instead of comparing against it
+    update: bool,
+    /// Fraction by which a step can be slower than the baseline
+    tolerance: f64,
+    /// Absolute slack in milliseconds, to keep fast steps from being flagged over noise
+    slack: f64,
+    /// Fail the run on regressions instead of only warning
+    fail: bool,
+}
+
+impl BaselineConfig {
+    fn from_env() -> Result<Option<Self>> {
+        let path = match dotenv::var("LATENCY_BASELINE") {
+            Ok(path) => path.into(),
+            Err(_) => return Ok(None),
+        };
+        let tolerance = env_or("LATENCY_TOLERANCE", 0.5)?;
+        let slack = env_or("LATENCY_SLACK", 20.0)?;
+
+        Ok(Some(BaselineConfig {
+            path,
+            update: dotenv::var("LATENCY_UPDATE").is_ok(),
+            tolerance,
+            slack,
+            fail: dotenv::var("LATENCY_FAIL").is_ok(),
+        }))
+    }
+}
+
+fn write(config: &BaselineConfig, medians: &BTreeMap<String, f64>) -> Result<()> {
+    let baseline: Map<String, Value> = medians
+        .iter()
+        .map(|(step, latency)| (step.clone(), Value::from(latency.round())))
+        .collect();
+    fs::write(
+        &config.path,
+        serde_json::to_string_pretty(&Value::Object(baseline))?,
+    )
+    .wrap_err_with(|| format!("Writing {}", config.path.display()))?;
+    Ok(())
+}
+
+/// Compare the recorded latencies against the baseline, returns false if the run should fail
+pub fn check(harness: &Harness) -> Result<bool> {
+    let config = match BaselineConfig::from_env()? {
+        Some(config) => config,
+        None => return Ok(true),
+    };
+    let medians = harness.timings().medians();
+
+    if config.update || !config.path.exists() {
+        write(&config, &medians)?;
+        println!(
+            " - {}",
+            format!("Latency baseline written to {}", config.path.display()).green()
+        );
+        return Ok(true);
+    }
+
+    let baseline: BTreeMap<String, f64> = serde_json::from_str(
+        &fs::read_to_string(&config.path)
+            .wrap_err_with(|| format!("Reading {}", config.path.display()))?,
+    )
+    .wrap_err("Invalid latency baseline")?;
+
+    println!(" - Latency baseline");
+    let mut regressions = 0;
+    for (step, latency) in &medians {
+        let expected = match baseline.get(step) {
+            Some(expected) => *expected,
+            None => continue,
+        };
+        if *latency > expected * (1.0 + config.tolerance) + config.slack {
+            regressions += 1;
+            let message = format!("{}: {:.0}ms, baseline {:.0}ms", step, latency, expected);
+            if config.fail {
+                println!("    - {}", message.red());
+            } else {
+                println!("    - {}", message.yellow());
+            }
+        }
+    }
+
+    if regressions == 0 {
+        println!("    - {}", "no regressions".green());
+    }
+
+    Ok(!(config.fail && regressions > 0))
+}
@@ -0,0 +1,27 @@
+//! Options read from environment variables
+
+use color_eyre::{eyre::WrapErr, Result};
+use std::error::Error;
+use std::str::FromStr;
+
+/// Parse the variable if it is set
+pub fn env_opt<T>(name: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Error + Send + Sync + 'static,
+{
+    dotenv::var(name)
+        .ok()
+        .map(|value| value.parse())
+        .transpose()
+        .wrap_err_with(|| format!("Invalid {}", name))
+}
+
+/// Parse the variable, falling back to `default` when it isn't set
+pub fn env_or<T>(name: &str, default: T) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Error + Send + Sync + 'static,
+{
+    Ok(env_opt(name)?.unwrap_or(default))
+}
@@ -0,0 +1,40 @@
+/// Size of the fixed demo header
+pub const HEADER_SIZE: usize = 1072;
+pub const HEADER_STRING_SIZE: usize = 260;
+pub const SERVER_OFFSET: usize = 16;
+pub const NICK_OFFSET: usize = SERVER_OFFSET + HEADER_STRING_SIZE;
+pub const MAP_OFFSET: usize = NICK_OFFSET + HEADER_STRING_SIZE;
+
+pub const GRANARY: &[u8] = include_bytes!("../data/granary.dem");
+pub const PROCESS: &[u8] = include_bytes!("../data/process.dem");
+pub const WARMFROST: &[u8] = include_bytes!("../data/warmfrost.dem");
+pub const RECONNECT: &[u8] = include_bytes!("../data/reconnect.dem");
+pub const ULTIDUO: &[u8] = include_bytes!("../data/ultiduo.dem");
+
+/// All fixture demos, by file name
+pub const ALL: &[(&str, &[u8])] = &[
+    ("granary.dem", GRANARY),
+    ("process.dem", PROCESS),
+    ("warmfrost.dem", WARMFROST),
+    ("reconnect.dem", RECONNECT),
+    ("ultiduo.dem", ULTIDUO),
+];
+
+/// Overwrite one of the string fields of the demo header, the field is only nul terminated if the
+/// string is shorter than the field
+pub fn set_header_string(data: &mut [u8], offset: usize, value: &[u8]) {
+    if data.len() >= offset + HEADER_STRING_SIZE {
+        let field = &mut data[offset..offset + HEADER_STRING_SIZE];
+        field.fill(0);
+        let length = value.len().min(HEADER_STRING_SIZE);
+        field[..length].copy_from_slice(&value[..length]);
+    }
+}
+
+/// Copy of a fixture with the server name replaced by `tag`, so it doesn't share its hash with
+/// other uploads of the same fixture
+pub fn unique(data: &[u8], tag: &str) -> Vec<u8> {
+    let mut data = data.to_vec();
+    set_header_string(&mut data, SERVER_OFFSET, tag.as_bytes());
+    data
+}
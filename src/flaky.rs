@@ -0,0 +1,70 @@
+//! Retries and flakiness tracking for test steps
+//!
+//! A failing step is retried up to `STEP_RETRIES` times, a step that only passes after a retry is
+//! reported as flaky instead of passed. When the tests are repeated with `--repeat N`, the failure
+//! rate of every step over all repeats is reported at the end of the run.
+
+use crate::harness::Harness;
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+pub enum StepOutcome {
+    Passed,
+    /// Passed after failing at least once
+    Flaky,
+    Failed,
+}
+
+#[derive(Default)]
+struct StepRecord {
+    runs: u32,
+    flaky: u32,
+    failures: u32,
+}
+
+/// Outcomes of every step by `<test>/<step>`, shared between all tests of a run
+#[derive(Clone, Default)]
+pub struct StepResults(Arc<Mutex<BTreeMap<String, StepRecord>>>);
+
+impl StepResults {
+    pub fn record(&self, test: &str, step: &str, outcome: StepOutcome) {
+        let mut results = self.0.lock().unwrap();
+        let record = results.entry(format!("{}/{}", test, step)).or_default();
+        record.runs += 1;
+        match outcome {
+            StepOutcome::Passed => {}
+            StepOutcome::Flaky => record.flaky += 1,
+            StepOutcome::Failed => record.failures += 1,
+        }
+    }
+}
+
+/// Print the steps that were flaky or failed in only some of the runs
+pub fn report(harness: &Harness) {
+    if harness.step_retries() == 0 && harness.repeat() <= 1 {
+        return;
+    }
+
+    println!(" - Flakiness");
+    let results = harness.step_results();
+    let results = results.0.lock().unwrap();
+    let mut flaky_steps = 0;
+    for (step, record) in results.iter() {
+        if record.flaky == 0 && (record.failures == 0 || record.failures == record.runs) {
+            continue;
+        }
+        flaky_steps += 1;
+        println!(
+            "    - {}: {} of {} runs failed ({:.0}%), {} passed after a retry",
+            step.yellow(),
+            record.failures,
+            record.runs,
+            record.failures as f64 / record.runs as f64 * 100.0,
+            record.flaky
+        );
+    }
+    if flaky_steps == 0 {
+        println!("    - {}", "no flaky steps".green());
+    }
+}
@@ -0,0 +1,37 @@
+use demostf_client::GameType;
+
+/// The game formats the api can filter on, with the number of players in a full game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Fours,
+    Sixes,
+    Prolander,
+    Highlander,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [
+        Format::Fours,
+        Format::Sixes,
+        Format::Prolander,
+        Format::Highlander,
+    ];
+
+    pub fn game_type(self) -> GameType {
+        match self {
+            Format::Fours => GameType::Fours,
+            Format::Sixes => GameType::Sixes,
+            Format::Prolander => GameType::Prolander,
+            Format::Highlander => GameType::HL,
+        }
+    }
+
+    pub fn player_count(self) -> usize {
+        match self {
+            Format::Fours => 8,
+            Format::Sixes => 12,
+            Format::Prolander => 14,
+            Format::Highlander => 18,
+        }
+    }
+}
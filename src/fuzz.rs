@@ -0,0 +1,329 @@
+//! Mutation based upload fuzzing
+//!
+//! Takes the fixture demos, applies random mutations to them and uploads the result, flagging any
+//! upload that causes a server error, hangs, or stores a demo that disagrees with what the parser
+//! reports for the mutated file.
+
+use crate::env::{env_opt, env_or};
+use crate::fixtures::{
+    self, HEADER_SIZE, HEADER_STRING_SIZE, MAP_OFFSET, NICK_OFFSET, SERVER_OFFSET,
+};
+use crate::harness::Harness;
+use crate::report::is_server_error;
+use crate::verify::{parse_demo, verify_demo};
+use color_eyre::{Report, Result};
+use colored::Colorize;
+use demostf_client::{ApiClient, Error};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::{timeout, Duration};
+
+/// Reset the database every so often to keep the api from slowing down
+const RESET_INTERVAL: u64 = 100;
+
+struct FuzzConfig {
+    /// Number of mutated demos to upload, runs until interrupted if not set
+    iterations: Option<u64>,
+    seed: u64,
+    crash_dir: PathBuf,
+    timeout: Duration,
+    minimize_attempts: usize,
+}
+
+impl FuzzConfig {
+    fn from_env() -> Result<Self> {
+        let iterations = env_opt("FUZZ_ITERATIONS")?;
+        let seed = env_or(
+            "FUZZ_SEED",
+            SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        )?;
+        let crash_dir = dotenv::var("FUZZ_CRASH_DIR").unwrap_or_else(|_| "fuzz-crashes".into());
+        let timeout = env_or("FUZZ_TIMEOUT", 30)?;
+        let minimize_attempts = env_or("FUZZ_MINIMIZE_ATTEMPTS", 200)?;
+
+        Ok(FuzzConfig {
+            iterations,
+            seed,
+            crash_dir: crash_dir.into(),
+            timeout: Duration::from_secs(timeout),
+            minimize_attempts,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Mutation {
+    BitFlip { offset: usize, bit: u8 },
+    SetByte { offset: usize, value: u8 },
+    Truncate { length: usize },
+    RemoveRange { start: usize, length: usize },
+    DuplicateRange { start: usize, length: usize },
+    HeaderString { offset: usize, value: Vec<u8> },
+}
+
+impl Mutation {
+    fn random(rng: &mut StdRng, data: &[u8]) -> Self {
+        let len = data.len().max(1);
+        match rng.gen_range(0..6) {
+            0 => Mutation::BitFlip {
+                offset: rng.gen_range(0..len),
+                bit: rng.gen_range(0..8),
+            },
+            1 => Mutation::SetByte {
+                offset: rng.gen_range(0..len),
+                value: [0, 0xff, 0x7f, 0x80][rng.gen_range(0..4)],
+            },
+            2 => Mutation::Truncate {
+                length: rng.gen_range(0..len),
+            },
+            3 => {
+                let start = rng.gen_range(HEADER_SIZE.min(len - 1)..len);
+                Mutation::RemoveRange {
+                    start,
+                    length: rng.gen_range(1..=(len - start).min(4096)),
+                }
+            }
+            4 => {
+                let start = rng.gen_range(HEADER_SIZE.min(len - 1)..len);
+                Mutation::DuplicateRange {
+                    start,
+                    length: rng.gen_range(1..=(len - start).min(4096)),
+                }
+            }
+            _ => Mutation::HeaderString {
+                offset: [SERVER_OFFSET, NICK_OFFSET, MAP_OFFSET][rng.gen_range(0..3)],
+                value: random_header_string(rng),
+            },
+        }
+    }
+
+    fn apply(&self, data: &mut Vec<u8>) {
+        match *self {
+            Mutation::BitFlip { offset, bit } => {
+                if let Some(byte) = data.get_mut(offset) {
+                    *byte ^= 1 << bit;
+                }
+            }
+            Mutation::SetByte { offset, value } => {
+                if let Some(byte) = data.get_mut(offset) {
+                    *byte = value;
+                }
+            }
+            Mutation::Truncate { length } => data.truncate(length),
+            Mutation::RemoveRange { start, length } => {
+                let start = start.min(data.len());
+                let end = (start + length).min(data.len());
+                data.drain(start..end);
+            }
+            Mutation::DuplicateRange { start, length } => {
+                let start = start.min(data.len());
+                let end = (start + length).min(data.len());
+                let range = data[start..end].to_vec();
+                data.splice(end..end, range);
+            }
+            Mutation::HeaderString { offset, ref value } => {
+                fixtures::set_header_string(data, offset, value)
+            }
+        }
+    }
+}
+
+/// Strings to write into the server/nick/map fields of the header
+fn random_header_string(rng: &mut StdRng) -> Vec<u8> {
+    match rng.gen_range(0..6) {
+        0 => Vec::new(),
+        1 => vec![b'a'; HEADER_STRING_SIZE],
+        2 => "cp_ünïcødé_🦀".as_bytes().to_vec(),
+        3 => b"'; DROP TABLE demos; --".to_vec(),
+        4 => b"../../../../etc/passwd".to_vec(),
+        _ => (0..rng.gen_range(1..HEADER_STRING_SIZE))
+            .map(|_| rng.gen())
+            .collect(),
+    }
+}
+
+#[derive(Debug)]
+enum Finding {
+    ServerError(String),
+    Hang(&'static str),
+    MetadataMismatch(Report),
+    AcceptedUnparsable(u32),
+}
+
+impl Finding {
+    /// Short name of the kind of finding, two inputs with the same kind are considered the same failure when minimizing
+    fn kind(&self) -> &'static str {
+        match self {
+            Finding::ServerError(_) => "server-error",
+            Finding::Hang(_) => "hang",
+            Finding::MetadataMismatch(_) => "metadata-mismatch",
+            Finding::AcceptedUnparsable(_) => "accepted-unparsable",
+        }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::ServerError(e) => write!(f, "server error: {}", e),
+            Finding::Hang(step) => write!(f, "{} timed out", step),
+            Finding::MetadataMismatch(e) => write!(f, "stored metadata mismatch: {:#}", e),
+            Finding::AcceptedUnparsable(id) => {
+                write!(f, "unparsable demo was stored as {}", id)
+            }
+        }
+    }
+}
+
+/// Upload the data and check the result for anything that indicates an api bug
+async fn check(client: &ApiClient, data: &[u8], limit: Duration) -> Option<Finding> {
+    let parsed = parse_demo(data).ok();
+
+    let upload = client.upload_demo(
+        String::from("fuzz.dem"),
+        data.to_vec(),
+        String::from("RED"),
+        String::from("BLUE"),
+        String::from("token"),
+    );
+    let id = match timeout(limit, upload).await {
+        Err(_) | Ok(Err(Error::TimeOut)) => return Some(Finding::Hang("upload")),
+        Ok(Err(e)) if is_server_error(&e) => return Some(Finding::ServerError(e.to_string())),
+        Ok(Err(_)) => return None,
+        Ok(Ok(id)) => id,
+    };
+
+    let demo = match timeout(limit, client.get(id)).await {
+        Err(_) | Ok(Err(Error::TimeOut)) => return Some(Finding::Hang("get demo")),
+        Ok(Err(e)) if is_server_error(&e) => return Some(Finding::ServerError(e.to_string())),
+        Ok(Err(e)) => return Some(Finding::MetadataMismatch(e.into())),
+        Ok(Ok(demo)) => demo,
+    };
+
+    match parsed {
+        Some((header, state)) => verify_demo(&demo, &header, &state)
+            .err()
+            .map(Finding::MetadataMismatch),
+        None => Some(Finding::AcceptedUnparsable(id)),
+    }
+}
+
+/// Shrink the input by removing chunks of it for as long as it keeps producing the same kind of finding
+async fn minimize(
+    client: &ApiClient,
+    kind: &str,
+    mut data: Vec<u8>,
+    config: &FuzzConfig,
+) -> Vec<u8> {
+    let mut attempts = 0;
+    let mut chunk = data.len() / 2;
+
+    while chunk > 0 && attempts < config.minimize_attempts {
+        let mut start = 0;
+        let mut reduced = false;
+        while start < data.len() && attempts < config.minimize_attempts {
+            let end = (start + chunk).min(data.len());
+            let candidate = [&data[..start], &data[end..]].concat();
+            attempts += 1;
+            match check(client, &candidate, config.timeout).await {
+                Some(finding) if finding.kind() == kind => {
+                    data = candidate;
+                    reduced = true;
+                }
+                _ => start += chunk,
+            }
+        }
+        if !reduced {
+            chunk /= 2;
+        }
+    }
+
+    data
+}
+
+fn save_crash(
+    config: &FuzzConfig,
+    name: &str,
+    input: &[u8],
+    minimized: &[u8],
+    mutations: &[Mutation],
+    finding: &Finding,
+) -> Result<()> {
+    fs::create_dir_all(&config.crash_dir)?;
+    fs::write(config.crash_dir.join(format!("{}.dem", name)), input)?;
+    fs::write(
+        config.crash_dir.join(format!("{}.min.dem", name)),
+        minimized,
+    )?;
+    fs::write(
+        config.crash_dir.join(format!("{}.txt", name)),
+        format!("seed: {}\n{}\n{:#?}\n", config.seed, finding, mutations),
+    )?;
+    Ok(())
+}
+
+/// Run the fuzzer, returns the number of findings
+pub async fn run(harness: &Harness) -> Result<u64> {
+    let config = FuzzConfig::from_env()?;
+    // give the client more time than our own timeout, so slow responses are reported as hangs
+    let client = harness.client_with_timeout(config.timeout * 2)?;
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut findings = 0;
+
+    println!(" - Fuzzing uploads with seed {}", config.seed);
+
+    let mut iteration = 0;
+    while !matches!(config.iterations, Some(max) if iteration >= max) {
+        if iteration % RESET_INTERVAL == 0 {
+            harness.reset().await?;
+        }
+
+        let (fixture, original) = fixtures::ALL[rng.gen_range(0..fixtures::ALL.len())];
+        let mut data = original.to_vec();
+        let mutations: Vec<Mutation> = (0..rng.gen_range(1..=8))
+            .map(|_| Mutation::random(&mut rng, &data))
+            .collect();
+        for mutation in &mutations {
+            mutation.apply(&mut data);
+        }
+
+        if let Some(finding) = check(&client, &data, config.timeout).await {
+            findings += 1;
+            println!(
+                "    - {}: {}",
+                format!("{} #{}", fixture, iteration).red(),
+                finding
+            );
+            let minimized = minimize(&client, finding.kind(), data.clone(), &config).await;
+            let name = format!("{}-{}", iteration, finding.kind());
+            save_crash(&config, &name, &data, &minimized, &mutations, &finding)?;
+            println!(
+                "      saved as {} ({} bytes minimized to {})",
+                name,
+                data.len(),
+                minimized.len()
+            );
+        }
+
+        iteration += 1;
+        if iteration % RESET_INTERVAL == 0 {
+            println!("    - {}", format!("{} inputs uploaded", iteration).green());
+        }
+    }
+
+    println!(
+        " - {} inputs uploaded, {} findings",
+        iteration,
+        if findings > 0 {
+            findings.to_string().red()
+        } else {
+            findings.to_string().green()
+        }
+    );
+
+    Ok(findings)
+}
@@ -0,0 +1,137 @@
+//! Diagnostics for steps that hang
+//!
+//! When a step times out and `STEP_TIMEOUT_DUMP` is set, the requests still in flight from the raw
+//! client, the `ApiClient` calls that haven't returned yet and the queries the api is still running
+//! in the database are printed, to help find out what the php-fpm workers are stuck on.
+//!
+//! `ApiClient` calls are tracked through the tracing spans the client creates for every call.
+
+use crate::raw::InFlight;
+use color_eyre::Result;
+use colored::Colorize;
+use sqlx::{Pool, Postgres};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+/// Description and start time of a call, by span id
+type Calls = BTreeMap<u64, (String, Instant)>;
+
+/// `ApiClient` calls that are currently running
+#[derive(Clone, Default)]
+pub struct ApiCalls {
+    calls: Arc<Mutex<Calls>>,
+}
+
+impl ApiCalls {
+    /// Start tracking the calls made by every `ApiClient`
+    pub fn install() -> Result<Self> {
+        let calls = ApiCalls::default();
+        tracing::subscriber::set_global_default(
+            tracing_subscriber::registry().with(calls.clone()),
+        )?;
+        Ok(calls)
+    }
+
+    /// Description and start time of every call that hasn't returned yet
+    pub fn calls(&self) -> Vec<(String, Instant)> {
+        self.calls.lock().unwrap().values().cloned().collect()
+    }
+}
+
+/// Formats the arguments of a call, leaving out the client itself and redacting api keys
+#[derive(Default)]
+struct Arguments(Vec<String>);
+
+impl Visit for Arguments {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        match field.name() {
+            "self" => {}
+            "key" => self.0.push(String::from("key: <redacted>")),
+            name => self.0.push(format!("{}: {:?}", name, value)),
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for ApiCalls {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+        let metadata = attrs.metadata();
+        if !metadata.target().starts_with("demostf_client") {
+            return;
+        }
+        let mut arguments = Arguments::default();
+        attrs.record(&mut arguments);
+        let call = format!("{}({})", metadata.name(), arguments.0.join(", "));
+        self.calls
+            .lock()
+            .unwrap()
+            .insert(id.into_u64(), (call, Instant::now()));
+    }
+
+    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+        self.calls.lock().unwrap().remove(&id.into_u64());
+    }
+}
+
+#[derive(Clone)]
+pub struct HangDump {
+    in_flight: InFlight,
+    api_calls: ApiCalls,
+    db: Pool<Postgres>,
+}
+
+impl HangDump {
+    pub fn new(in_flight: InFlight, api_calls: ApiCalls, db: Pool<Postgres>) -> Self {
+        HangDump {
+            in_flight,
+            api_calls,
+            db,
+        }
+    }
+
+    pub async fn print(&self) {
+        let now = Instant::now();
+        for (call, start) in self.api_calls.calls() {
+            println!(
+                "      {}: {} ({:.1}s)",
+                "api call".yellow(),
+                call,
+                (now - start).as_secs_f64()
+            );
+        }
+        for (method, url, start) in self.in_flight.requests() {
+            println!(
+                "      {}: {} {} ({:.1}s)",
+                "in flight".yellow(),
+                method,
+                url,
+                (now - start).as_secs_f64()
+            );
+        }
+
+        let queries: Result<Vec<(f64, String)>, _> = sqlx::query_as(
+            "SELECT EXTRACT(EPOCH FROM now() - query_start)::float8, query FROM pg_stat_activity \
+            WHERE state = 'active' AND pid <> pg_backend_pid() AND datname = current_database()",
+        )
+        .fetch_all(&self.db)
+        .await;
+        match queries {
+            Ok(queries) => {
+                for (duration, query) in queries {
+                    println!(
+                        "      {}: {} ({:.1}s)",
+                        "active query".yellow(),
+                        query,
+                        duration
+                    );
+                }
+            }
+            Err(e) => println!("      {}: {}", "failed to list active queries".red(), e),
+        }
+    }
+}
@@ -1,23 +1,88 @@
-use color_eyre::Result;
+use crate::baseline::Timings;
+use crate::flaky::StepResults;
+use crate::hang::{ApiCalls, HangDump};
+use crate::raw::RawClient;
+use crate::schema::Schema;
+use crate::tags::TagFilter;
+use color_eyre::{eyre::WrapErr, Result};
 use demostf_client::ApiClient;
+use reqwest::StatusCode;
+use serde_json::Value;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::{Pool, Postgres};
+use std::collections::BTreeSet;
+use std::time::Duration;
+use time::OffsetDateTime;
 
 pub struct Harness {
+    base_url: String,
     client: ApiClient,
+    raw: RawClient,
+    schema: Schema,
     db: Pool<Postgres>,
+    timings: Timings,
+    step_results: StepResults,
+    step_retries: u32,
+    repeat: u32,
+    tag_filter: TagFilter,
+    step_timeout: Duration,
+    hang_dump: Option<ApiCalls>,
 }
 
 impl Harness {
     pub async fn new(base_url: &str, db_url: &str) -> Result<Self> {
         let client = ApiClient::with_base_url(base_url)?;
+        let raw = RawClient::new(base_url);
         let db = PgPoolOptions::new()
             .max_connections(5)
             .connect(db_url)
             .await
             .unwrap();
 
-        Ok(Harness { client, db })
+        Ok(Harness {
+            base_url: base_url.into(),
+            client,
+            raw,
+            schema: Schema::load()?,
+            db,
+            timings: Timings::default(),
+            step_results: StepResults::default(),
+            step_retries: 0,
+            repeat: 1,
+            tag_filter: TagFilter::default(),
+            step_timeout: Duration::from_secs(60),
+            hang_dump: None,
+        })
+    }
+
+    /// Retry failing steps up to `retries` times
+    pub fn with_step_retries(mut self, retries: u32) -> Self {
+        self.step_retries = retries;
+        self
+    }
+
+    /// Default time after which a step is considered hanging
+    pub fn with_step_timeout(mut self, timeout: Duration) -> Self {
+        self.step_timeout = timeout;
+        self
+    }
+
+    /// Print the in flight requests, running api calls and active queries when a step times out
+    pub fn with_hang_dump(mut self, hang_dump: Option<ApiCalls>) -> Self {
+        self.hang_dump = hang_dump;
+        self
+    }
+
+    /// Only run the tests selected by the filter
+    pub fn with_tag_filter(mut self, tag_filter: TagFilter) -> Self {
+        self.tag_filter = tag_filter;
+        self
+    }
+
+    /// Run every test `repeat` times
+    pub fn with_repeat(mut self, repeat: u32) -> Self {
+        self.repeat = repeat.max(1);
+        self
     }
 
     pub async fn reset(&self) -> Result<()> {
@@ -50,7 +115,263 @@ impl Harness {
         Ok(())
     }
 
+    pub async fn add_user(&self, steam_id: u64, name: &str, token: &str) -> Result<()> {
+        sqlx::query("INSERT INTO users(steamid, name, avatar, token) VALUES($1, $2, '', $3)")
+            .bind(steam_id as i64)
+            .bind(name)
+            .bind(token)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn set_user_name(&self, steam_id: u64, name: &str) -> Result<()> {
+        sqlx::query("UPDATE users SET name = $1 WHERE steamid::text = $2")
+            .bind(name)
+            .bind(steam_id.to_string())
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Block all uploads from the user with the provided steam id
+    pub async fn blacklist_uploader(&self, steam_id: u64) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO upload_blacklist(uploader_id) SELECT id FROM users WHERE steamid::text = $1",
+        )
+        .bind(steam_id.to_string())
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Insert `count` copies of an uploaded demo directly into the database, each with a unique hash
+    ///
+    /// Only the demo row is copied, the copies have no players or chat
+    pub async fn clone_demo(&self, id: u32, count: u32) -> Result<()> {
+        let mut transaction = self.db.begin().await?;
+
+        sqlx::query(
+            "CREATE TEMPORARY TABLE demo_clone ON COMMIT DROP AS SELECT * FROM demos WHERE id = $1",
+        )
+        .bind(id as i32)
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query("ALTER TABLE demo_clone DROP COLUMN id")
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query("INSERT INTO demos SELECT nextval('demos_id_seq'), demo_clone.* FROM demo_clone, generate_series(1, $1)")
+            .bind(count as i32)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query("UPDATE demos SET hash = md5(id::text) WHERE hash = (SELECT hash FROM demos WHERE id = $1) AND id != $1")
+            .bind(id as i32)
+            .execute(&mut transaction)
+            .await?;
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Overwrite the upload time of a demo
+    pub async fn set_upload_time(&self, id: u32, time: OffsetDateTime) -> Result<()> {
+        sqlx::query("UPDATE demos SET created_at = to_timestamp($1) WHERE id = $2")
+            .bind(time.unix_timestamp())
+            .bind(id as i32)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Overwrite until when a demo is private, a time in the past makes the demo public
+    pub async fn set_private_until(&self, id: u32, time: OffsetDateTime) -> Result<()> {
+        sqlx::query("UPDATE demos SET private_until = to_timestamp($1) WHERE id = $2")
+            .bind(time.unix_timestamp())
+            .bind(id as i32)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Overwrite the player count of a demo, for testing formats there are no fixtures for
+    pub async fn set_player_count(&self, id: u32, count: u8) -> Result<()> {
+        sqlx::query(r#"UPDATE demos SET "playerCount" = $1 WHERE id = $2"#)
+            .bind(count as i32)
+            .bind(id as i32)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Issue a storage key that allows setting the url of demos for a single backend
+    pub async fn add_storage_key(
+        &self,
+        backend: &str,
+        key: &str,
+        expires: Option<OffsetDateTime>,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO storage_keys(backend, key, expires_at) VALUES($1, $2, to_timestamp($3))",
+        )
+        .bind(backend)
+        .bind(key)
+        .bind(expires.map(OffsetDateTime::unix_timestamp))
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn revoke_storage_key(&self, key: &str) -> Result<()> {
+        sqlx::query("DELETE FROM storage_keys WHERE key = $1")
+            .bind(key)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn team_names(&self) -> Result<Vec<String>> {
+        Ok(sqlx::query_scalar("SELECT name FROM teams ORDER BY id")
+            .fetch_all(&self.db)
+            .await?)
+    }
+
+    pub async fn count_rows(&self, table: &str) -> Result<i64> {
+        Ok(
+            sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+                .fetch_one(&self.db)
+                .await?,
+        )
+    }
+
+    /// Number of distinct values for the columns that occur in more than one row
+    pub async fn count_duplicates(&self, table: &str, columns: &str) -> Result<i64> {
+        Ok(sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM (SELECT {columns} FROM {table} GROUP BY {columns} HAVING COUNT(*) > 1) duplicates",
+            table = table,
+            columns = columns
+        ))
+        .fetch_one(&self.db)
+        .await?)
+    }
+
+    /// The lowest and highest id in a table
+    pub async fn id_range(&self, table: &str) -> Result<(i64, i64)> {
+        Ok(sqlx::query_as(&format!(
+            "SELECT COALESCE(MIN(id), 0)::bigint, COALESCE(MAX(id), 0)::bigint FROM {}",
+            table
+        ))
+        .fetch_one(&self.db)
+        .await?)
+    }
+
+    /// Total size in bytes and number of dead tuples for every table
+    pub async fn table_stats(&self) -> Result<Vec<(String, i64, i64)>> {
+        Ok(sqlx::query_as(
+            "SELECT relname::text, pg_total_relation_size(relid), n_dead_tup FROM pg_stat_user_tables ORDER BY relname",
+        )
+        .fetch_all(&self.db)
+        .await?)
+    }
+
+    /// Number of open connections to the database, including the ones from the harness
+    pub async fn connection_count(&self) -> Result<i64> {
+        Ok(sqlx::query_scalar(
+            "SELECT COUNT(*) FROM pg_stat_activity WHERE datname = current_database()",
+        )
+        .fetch_one(&self.db)
+        .await?)
+    }
+
     pub fn client(&self) -> ApiClient {
         self.client.clone()
     }
+
+    /// A client that waits up to `timeout` for a response, instead of the default 15 seconds
+    pub fn client_with_timeout(&self, timeout: Duration) -> Result<ApiClient> {
+        Ok(ApiClient::with_base_url_and_timeout(
+            self.base_url.as_str(),
+            timeout,
+        )?)
+    }
+
+    /// Fetch the demos, chat and users the api currently serves and validate them against the
+    /// schema, so the responses that tests only see through the typed client are checked too
+    ///
+    /// Only the demos on the first page of the list are fetched, to keep this fast after tests
+    /// that seed a lot of demos
+    pub async fn validate_responses(&self) -> Result<()> {
+        let list = self.fetch_valid("demos", &[], "demoList").await?;
+        let mut uploaders = BTreeSet::new();
+        for demo in list.as_array().into_iter().flatten() {
+            self.fetch_valid(&format!("demos/{}", demo["id"]), &[], "demo")
+                .await?;
+            self.fetch_valid(&format!("demos/{}/chat", demo["id"]), &[], "chat")
+                .await?;
+            uploaders.extend(demo["uploader"].as_u64());
+        }
+
+        for uploader in uploaders {
+            let user = self
+                .fetch_valid(&format!("users/{}", uploader), &[], "user")
+                .await?;
+            if let Some(steam_id) = user["steamid"].as_str() {
+                self.fetch_valid(&format!("uploads/{}", steam_id), &[], "demoList")
+                    .await?;
+            }
+            if let Some(name) = user["name"].as_str() {
+                self.fetch_valid("users/search", &[("query", name)], "userList")
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn fetch_valid(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        definition: &str,
+    ) -> Result<Value> {
+        let response = self.raw.get_query(path, query).await?;
+        response.expect_status(StatusCode::OK)?;
+        let value = response.json()?;
+        self.schema
+            .validate(definition, &value)
+            .wrap_err_with(|| format!("Invalid response for {}", path))?;
+        Ok(value)
+    }
+
+    pub fn raw(&self) -> RawClient {
+        self.raw.clone()
+    }
+
+    pub fn timings(&self) -> Timings {
+        self.timings.clone()
+    }
+
+    pub fn step_results(&self) -> StepResults {
+        self.step_results.clone()
+    }
+
+    pub fn step_retries(&self) -> u32 {
+        self.step_retries
+    }
+
+    pub fn repeat(&self) -> u32 {
+        self.repeat
+    }
+
+    pub fn tag_filter(&self) -> &TagFilter {
+        &self.tag_filter
+    }
+
+    pub fn step_timeout(&self) -> Duration {
+        self.step_timeout
+    }
+
+    pub fn hang_dump(&self) -> Option<HangDump> {
+        self.hang_dump
+            .clone()
+            .map(|api_calls| HangDump::new(self.raw.in_flight(), api_calls, self.db.clone()))
+    }
 }
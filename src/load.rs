@@ -0,0 +1,307 @@
+//! Load testing
+//!
+//! Drives a mix of concurrent upload, get, list and chat requests against the api for a fixed
+//! duration or number of requests and reports the throughput, latency percentiles and error rate
+//! for every endpoint.
+
+use crate::env::{env_opt, env_or};
+use crate::fixtures;
+use crate::harness::Harness;
+use color_eyre::{eyre::WrapErr, Report, Result};
+use colored::Colorize;
+use demostf_client::{ApiClient, ListParams};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+struct LoadConfig {
+    concurrency: u64,
+    /// Total number of requests to send, if set the duration is ignored
+    requests: Option<u64>,
+    duration: Duration,
+    /// Relative weights of upload, get, list and chat requests
+    mix: [u32; 4],
+}
+
+impl LoadConfig {
+    fn from_env() -> Result<Self> {
+        let concurrency = env_or("LOAD_CONCURRENCY", 10)?;
+        let requests = env_opt("LOAD_REQUESTS")?;
+        let duration = env_or("LOAD_DURATION", 60)?;
+        let mix = match dotenv::var("LOAD_MIX") {
+            Ok(mix) => parse_mix(&mix).wrap_err("Invalid LOAD_MIX")?,
+            Err(_) => [1, 4, 4, 1],
+        };
+
+        Ok(LoadConfig {
+            concurrency,
+            requests,
+            duration: Duration::from_secs(duration),
+            mix,
+        })
+    }
+}
+
+/// Parse the request mix in the form of `upload,get,list,chat`
+fn parse_mix(mix: &str) -> Result<[u32; 4]> {
+    let weights = mix
+        .split(',')
+        .map(|weight| weight.trim().parse())
+        .collect::<Result<Vec<u32>, _>>()?;
+    match weights.as_slice() {
+        &[upload, get, list, chat] if upload + get + list + chat > 0 => {
+            Ok([upload, get, list, chat])
+        }
+        _ => Err(Report::msg(
+            "Expected four weights for upload, get, list and chat",
+        )),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Endpoint {
+    Upload,
+    Get,
+    List,
+    Chat,
+}
+
+impl Endpoint {
+    const ALL: [Endpoint; 4] = [
+        Endpoint::Upload,
+        Endpoint::Get,
+        Endpoint::List,
+        Endpoint::Chat,
+    ];
+
+    fn pick(rng: &mut StdRng, mix: &[u32; 4]) -> Self {
+        let mut choice = rng.gen_range(0..mix.iter().sum::<u32>());
+        for (endpoint, weight) in Endpoint::ALL.iter().zip(mix.iter()) {
+            if choice < *weight {
+                return *endpoint;
+            }
+            choice -= weight;
+        }
+        unreachable!()
+    }
+}
+
+#[derive(Default)]
+struct EndpointStats {
+    latencies: Vec<Duration>,
+    errors: u64,
+}
+
+impl EndpointStats {
+    fn merge(&mut self, other: EndpointStats) {
+        self.latencies.extend(other.latencies);
+        self.errors += other.errors;
+    }
+
+    fn requests(&self) -> u64 {
+        self.latencies.len() as u64 + self.errors
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    endpoints: [EndpointStats; 4],
+}
+
+impl Stats {
+    fn endpoint(&mut self, endpoint: Endpoint) -> &mut EndpointStats {
+        &mut self.endpoints[endpoint as usize]
+    }
+
+    fn merge(&mut self, other: Stats) {
+        for (stats, other) in self.endpoints.iter_mut().zip(other.endpoints) {
+            stats.merge(other);
+        }
+    }
+}
+
+/// The demos available for get and chat requests
+struct Targets {
+    ids: Vec<u32>,
+    /// Counter to give every uploaded demo a unique hash
+    uploads: AtomicU64,
+}
+
+async fn request(
+    client: &ApiClient,
+    targets: &Targets,
+    rng: &mut StdRng,
+    endpoint: Endpoint,
+) -> Result<()> {
+    match endpoint {
+        Endpoint::Upload => {
+            let (name, data) = fixtures::ALL[rng.gen_range(0..fixtures::ALL.len())];
+            let upload = targets.uploads.fetch_add(1, Ordering::Relaxed);
+            let data = fixtures::unique(data, &format!("load test {}", upload));
+            client
+                .upload_demo(
+                    String::from(name),
+                    data,
+                    String::from("RED"),
+                    String::from("BLUE"),
+                    String::from("token"),
+                )
+                .await?;
+        }
+        Endpoint::Get => {
+            let id = targets.ids[rng.gen_range(0..targets.ids.len())];
+            client.get(id).await?;
+        }
+        Endpoint::List => {
+            client
+                .list(ListParams::default(), rng.gen_range(1..=3))
+                .await?;
+        }
+        Endpoint::Chat => {
+            let id = targets.ids[rng.gen_range(0..targets.ids.len())];
+            client.get_chat(id).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn worker(
+    client: ApiClient,
+    targets: Arc<Targets>,
+    mix: [u32; 4],
+    seed: u64,
+    remaining: Arc<AtomicU64>,
+    deadline: Instant,
+) -> Stats {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut stats = Stats::default();
+
+    while Instant::now() < deadline
+        && remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok()
+    {
+        let endpoint = Endpoint::pick(&mut rng, &mix);
+        let start = Instant::now();
+        match request(&client, &targets, &mut rng, endpoint).await {
+            Ok(()) => stats.endpoint(endpoint).latencies.push(start.elapsed()),
+            Err(_) => stats.endpoint(endpoint).errors += 1,
+        }
+    }
+
+    stats
+}
+
+fn percentile(sorted: &[Duration], percentile: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::default();
+    }
+    sorted[(sorted.len() * percentile / 100).min(sorted.len() - 1)]
+}
+
+fn report(stats: &mut Stats, elapsed: Duration) {
+    let total: u64 = stats.endpoints.iter().map(EndpointStats::requests).sum();
+    println!(
+        " - {} requests in {:.1}s, {:.1} requests/s",
+        total,
+        elapsed.as_secs_f64(),
+        total as f64 / elapsed.as_secs_f64()
+    );
+
+    for endpoint in Endpoint::ALL.iter().copied() {
+        let stats = stats.endpoint(endpoint);
+        let requests = stats.requests();
+        if requests == 0 {
+            continue;
+        }
+        stats.latencies.sort_unstable();
+        let error_rate = stats.errors as f64 / requests as f64 * 100.0;
+        let error_rate = format!("{:.1}% errors", error_rate);
+
+        println!(
+            "    - {:?}: {} requests, {:.1}/s, p50 {}ms, p95 {}ms, p99 {}ms, {}",
+            endpoint,
+            requests,
+            requests as f64 / elapsed.as_secs_f64(),
+            percentile(&stats.latencies, 50).as_millis(),
+            percentile(&stats.latencies, 95).as_millis(),
+            percentile(&stats.latencies, 99).as_millis(),
+            if stats.errors > 0 {
+                error_rate.red()
+            } else {
+                error_rate.green()
+            }
+        );
+    }
+}
+
+pub async fn run(harness: &Harness) -> Result<()> {
+    let config = LoadConfig::from_env()?;
+    let client = harness.client();
+
+    harness.reset().await?;
+    let mut ids = Vec::with_capacity(fixtures::ALL.len());
+    for (name, data) in fixtures::ALL {
+        ids.push(
+            client
+                .upload_demo(
+                    String::from(*name),
+                    data.to_vec(),
+                    String::from("RED"),
+                    String::from("BLUE"),
+                    String::from("token"),
+                )
+                .await?,
+        );
+    }
+    let targets = Arc::new(Targets {
+        ids,
+        uploads: AtomicU64::new(0),
+    });
+
+    match config.requests {
+        Some(requests) => println!(
+            " - Sending {} requests with {} concurrent clients",
+            requests, config.concurrency
+        ),
+        None => println!(
+            " - Sending requests for {}s with {} concurrent clients",
+            config.duration.as_secs(),
+            config.concurrency
+        ),
+    }
+
+    let start = Instant::now();
+    let deadline = match config.requests {
+        // the deadline is only a safety net when sending a fixed number of requests
+        Some(_) => start + Duration::from_secs(24 * 60 * 60),
+        None => start + config.duration,
+    };
+    let remaining = Arc::new(AtomicU64::new(config.requests.unwrap_or(u64::MAX)));
+
+    let workers: Vec<_> = (0..config.concurrency)
+        .map(|seed| {
+            tokio::spawn(worker(
+                client.clone(),
+                targets.clone(),
+                config.mix,
+                seed,
+                remaining.clone(),
+                deadline,
+            ))
+        })
+        .collect();
+
+    let mut stats = Stats::default();
+    for worker in workers {
+        stats.merge(worker.await?);
+    }
+
+    report(&mut stats, start.elapsed());
+
+    Ok(())
+}
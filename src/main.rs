@@ -1,31 +1,119 @@
-mod harness;
+#[macro_use]
 mod report;
 
+mod baseline;
+mod env;
+mod fixtures;
+mod flaky;
+mod format;
+mod fuzz;
+mod hang;
+mod harness;
+mod load;
+mod properties;
+mod raw;
+mod schema;
+mod soak;
+mod storage;
+mod suites;
+mod tags;
+mod verify;
+
+use crate::env::env_or;
+use crate::hang::ApiCalls;
 use crate::harness::Harness;
+use crate::tags::{Tag, TagFilter};
+use crate::verify::{parse_demo, verify_chat, verify_demo};
 use color_eyre::{eyre::WrapErr, Report, Result};
-use demostf_client::{ChatMessage, Class, Demo, GameType, ListOrder, ListParams, SteamID, Team};
+use demostf_client::{GameType, ListOrder, ListParams, SteamID};
 use report::{assert_eq, Test};
-use std::convert::TryFrom;
-use tf_demo_parser::{demo::header::Header, DemoParser, MatchState};
-use tokio::time::Duration;
+use time::{Duration, OffsetDateTime};
 
-macro_rules! assert_object_eq {
-    ($obj:expr => { $($name:ident == $value:expr),* }) => {
-        $(report::assert_eq_borrow(&$obj.$name, $value)?;)*
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let repeat = match arg_value(&args, "--repeat")? {
+        Some(repeat) => repeat.parse().wrap_err("Invalid --repeat")?,
+        None => 1,
     };
-    ($obj:expr => { $($name:ident == $value:expr),* , }) => {
-        $(report::assert_eq_borrow(&$obj.$name, $value)?;)*
+    let tag_filter = TagFilter::parse(
+        arg_value(&args, "--tags")?,
+        arg_value(&args, "--exclude-tags")?,
+        arg_value(&args, "--suite")?,
+    )?;
+    let step_retries = env_or("STEP_RETRIES", 0)?;
+    let step_timeout = env_or("STEP_TIMEOUT", 60)?;
+    let api_calls = match dotenv::var("STEP_TIMEOUT_DUMP") {
+        Ok(_) => Some(ApiCalls::install()?),
+        Err(_) => None,
     };
+
+    let harness = Harness::new(&dotenv::var("BASE_URL")?, &dotenv::var("DB_URL")?)
+        .await?
+        .with_step_retries(step_retries)
+        .with_step_timeout(std::time::Duration::from_secs(step_timeout))
+        .with_hang_dump(api_calls)
+        .with_repeat(repeat)
+        .with_tag_filter(tag_filter);
+    let harness = &harness;
+
+    match args.get(1).map(String::as_str) {
+        Some("fuzz") => {
+            let findings = fuzz::run(harness).await?;
+            if findings > 0 {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some("load") => {
+            return load::run(harness).await;
+        }
+        Some("soak") => {
+            let flagged = soak::run(harness).await?;
+            if flagged > 0 {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some("properties") => {
+            let failures = properties::run(harness).await?;
+            if failures > 0 {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let mut success = run_tests(harness).await?;
+    success &= baseline::check(harness)?;
+    flaky::report(harness);
+    if !success {
+        std::process::exit(1);
+    }
+
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let harness = Harness::new(&dotenv::var("BASE_URL")?, &dotenv::var("DB_URL")?).await?;
-    let granary_data = include_bytes!("../data/granary.dem");
-    let process_data = include_bytes!("../data/process.dem");
-    let warmfrost_data = include_bytes!("../data/warmfrost.dem");
-    let reconnect_data = include_bytes!("../data/reconnect.dem");
-    let ultiduo_data = include_bytes!("../data/ultiduo.dem");
+/// Value of a `--flag value` command line option
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) => {
+            Ok(Some(args.get(index + 1).ok_or_else(|| {
+                Report::msg(format!("Missing value for {}", flag))
+            })?))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Run all tests, returns whether all tests passed
+async fn run_tests(harness: &Harness) -> Result<bool> {
+    let granary_data = fixtures::GRANARY;
+    let process_data = fixtures::PROCESS;
+    let warmfrost_data = fixtures::WARMFROST;
+    let reconnect_data = fixtures::RECONNECT;
+    let ultiduo_data = fixtures::ULTIDUO;
 
     let edit_key = dotenv::var("EDIT_KEY")?;
     let edit_key = &edit_key;
@@ -36,7 +124,8 @@ async fn main() -> Result<()> {
 
     success &= Test::run(
         "Upload with invalid credentials",
-        &harness,
+        &[Tag::Upload, Tag::Auth],
+        harness,
         |test| async move {
             test.step("upload", |client| async move {
                 let result = client
@@ -66,13 +155,11 @@ async fn main() -> Result<()> {
 
     success &= Test::run(
         "Upload demo, then retrieve info",
-        &harness,
+        &[Tag::Upload],
+        harness,
         |test| async move {
-            let demo = tf_demo_parser::Demo::new(granary_data);
-            let parser = DemoParser::new(demo.get_stream());
-            let (header, state) = parser
-                .parse()
-                .map_err(|_| Report::msg("Failed to parse demo"))?;
+            let (header, state) = parse_demo(granary_data)?;
+            let header = &header;
             let state = &state;
 
             let id = test
@@ -103,7 +190,7 @@ async fn main() -> Result<()> {
                     url == "https://localhost/ec/68/ec681d1b4846a7e3cb2c129fcbd858ac_test.dem",
                     backend == "static",
                 });
-                verify_demo(&demo, &header, state)?;
+                verify_demo(&demo, header, state)?;
                 assert_eq(demo.uploader.id(), 1)?;
 
                 let uploader = demo.uploader.resolve(client).await?;
@@ -174,272 +261,319 @@ async fn main() -> Result<()> {
     )
     .await;
 
-    success &= Test::run("Listings", &harness, |test| async move {
-        test.step("upload", |client| async move {
-            client
-                .upload_demo(
-                    String::from("test1.dem"),
-                    granary_data.to_vec(),
-                    String::from("RED"),
-                    String::from("BLUE"),
-                    String::from("token"),
-                )
-                .await?;
-            client
-                .upload_demo(
-                    String::from("test2.dem"),
-                    process_data.to_vec(),
-                    String::from("RED"),
-                    String::from("BLUE"),
-                    String::from("token"),
-                )
-                .await?;
+    success &= Test::run(
+        "Listings",
+        &[Tag::Listing, Tag::Destructive],
+        harness,
+        |test| async move {
+            test.step("upload", |client| async move {
+                client
+                    .upload_demo(
+                        String::from("test1.dem"),
+                        granary_data.to_vec(),
+                        String::from("RED"),
+                        String::from("BLUE"),
+                        String::from("token"),
+                    )
+                    .await?;
+                client
+                    .upload_demo(
+                        String::from("test2.dem"),
+                        process_data.to_vec(),
+                        String::from("RED"),
+                        String::from("BLUE"),
+                        String::from("token"),
+                    )
+                    .await?;
 
-            // ensure there is some time separation
-            std::thread::sleep(Duration::from_secs(1));
+                client
+                    .upload_demo(
+                        String::from("test3.dem"),
+                        warmfrost_data.to_vec(),
+                        String::from("RED"),
+                        String::from("BLUE"),
+                        String::from("token"),
+                    )
+                    .await?;
 
-            client
-                .upload_demo(
-                    String::from("test3.dem"),
-                    warmfrost_data.to_vec(),
-                    String::from("RED"),
-                    String::from("BLUE"),
-                    String::from("token"),
-                )
-                .await?;
+                client
+                    .upload_demo(
+                        String::from("test4.dem"),
+                        reconnect_data.to_vec(),
+                        String::from("RED"),
+                        String::from("BLUE"),
+                        String::from("token"),
+                    )
+                    .await?;
+                client
+                    .upload_demo(
+                        String::from("test5.dem"),
+                        ultiduo_data.to_vec(),
+                        String::from("RED"),
+                        String::from("BLUE"),
+                        String::from("token"),
+                    )
+                    .await?;
+                Ok(())
+            })
+            .await?;
 
-            // ensure there is some time separation
-            std::thread::sleep(Duration::from_secs(1));
+            // set the upload times directly so the time filters can be tested without waiting between uploads
+            let start = OffsetDateTime::from_unix_timestamp(1_600_000_000)?;
+            test.step("set upload times", |_| async move {
+                harness.set_upload_time(1, start).await?;
+                harness.set_upload_time(2, start).await?;
+                harness
+                    .set_upload_time(3, start + Duration::minutes(1))
+                    .await?;
+                harness
+                    .set_upload_time(4, start + Duration::minutes(2))
+                    .await?;
+                harness
+                    .set_upload_time(5, start + Duration::minutes(2))
+                    .await?;
+                Ok(())
+            })
+            .await?;
 
-            client
-                .upload_demo(
-                    String::from("test4.dem"),
-                    reconnect_data.to_vec(),
-                    String::from("RED"),
-                    String::from("BLUE"),
-                    String::from("token"),
-                )
-                .await?;
-            client
-                .upload_demo(
-                    String::from("test5.dem"),
-                    ultiduo_data.to_vec(),
-                    String::from("RED"),
-                    String::from("BLUE"),
-                    String::from("token"),
-                )
-                .await?;
-            Ok(())
-        })
-        .await?;
+            test.step("list defaults", |client| async move {
+                let list = client.list(ListParams::default(), 1).await?;
+                assert_eq(list.len(), 5)?;
+                assert_eq(list[0].id, 5)?;
+                assert_eq(list[1].id, 4)?;
+                assert_eq(list[2].id, 3)?;
+                assert_eq(list[3].id, 2)?;
+                assert_eq(list[4].id, 1)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list defaults", |client| async move {
-            let list = client.list(ListParams::default(), 1).await?;
-            assert_eq(list.len(), 5)?;
-            assert_eq(list[0].id, 5)?;
-            assert_eq(list[1].id, 4)?;
-            assert_eq(list[2].id, 3)?;
-            assert_eq(list[3].id, 2)?;
-            assert_eq(list[4].id, 1)?;
-            Ok(())
-        })
-        .await?;
+            test.step("list asc", |client| async move {
+                let list = client
+                    .list(ListParams::default().with_order(ListOrder::Ascending), 1)
+                    .await?;
+                assert_eq(list.len(), 5)?;
+                assert_eq(list[0].id, 1)?;
+                assert_eq(list[1].id, 2)?;
+                assert_eq(list[2].id, 3)?;
+                assert_eq(list[3].id, 4)?;
+                assert_eq(list[4].id, 5)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list asc", |client| async move {
-            let list = client
-                .list(ListParams::default().with_order(ListOrder::Ascending), 1)
-                .await?;
-            assert_eq(list.len(), 5)?;
-            assert_eq(list[0].id, 1)?;
-            assert_eq(list[1].id, 2)?;
-            assert_eq(list[2].id, 3)?;
-            assert_eq(list[3].id, 4)?;
-            assert_eq(list[4].id, 5)?;
-            Ok(())
-        })
-        .await?;
+            test.step("list map filter", |client| async move {
+                let list = client
+                    .list(ListParams::default().with_map("cp_process"), 1)
+                    .await?;
+                assert_eq(list.len(), 1)?;
+                assert_eq(list[0].id, 2)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list map filter", |client| async move {
-            let list = client
-                .list(ListParams::default().with_map("cp_process"), 1)
-                .await?;
-            assert_eq(list.len(), 1)?;
-            assert_eq(list[0].id, 2)?;
-            Ok(())
-        })
-        .await?;
+            test.step("list single player filter", |client| async move {
+                let list = client
+                    .list(
+                        ListParams::default().with_players(vec![76561197992327511]),
+                        1,
+                    )
+                    .await?;
+                assert_eq(list.len(), 3)?;
+                assert_eq(list[0].id, 5)?;
+                assert_eq(list[1].id, 4)?;
+                assert_eq(list[2].id, 1)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list single player filter", |client| async move {
-            let list = client
-                .list(
-                    ListParams::default().with_players(vec![76561197992327511]),
-                    1,
-                )
-                .await?;
-            assert_eq(list.len(), 3)?;
-            assert_eq(list[0].id, 5)?;
-            assert_eq(list[1].id, 4)?;
-            assert_eq(list[2].id, 1)?;
-            Ok(())
-        })
-        .await?;
+            test.step("list multiple player filter", |client| async move {
+                let list = client
+                    .list(
+                        ListParams::default()
+                            .with_players(vec![76561197992327511, 76561198024494988]),
+                        1,
+                    )
+                    .await?;
+                assert_eq(list.len(), 2)?;
+                assert_eq(list[0].id, 4)?;
+                assert_eq(list[1].id, 1)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list multiple player filter", |client| async move {
-            let list = client
-                .list(
-                    ListParams::default().with_players(vec![76561197992327511, 76561198024494988]),
-                    1,
-                )
-                .await?;
-            assert_eq(list.len(), 2)?;
-            assert_eq(list[0].id, 4)?;
-            assert_eq(list[1].id, 1)?;
-            Ok(())
-        })
-        .await?;
+            test.step("list reconnected player filter", |client| async move {
+                let list = client
+                    .list(
+                        ListParams::default().with_players(vec![
+                            76561198024494988,
+                            76561198011495003,
+                            76561197998883586, // <- reconnected
+                        ]),
+                        1,
+                    )
+                    .await?;
+                assert_eq(list.len(), 1)?;
+                assert_eq(list[0].id, 4)?;
+
+                let players = list[0].get_players(client).await?;
+                let reconnected_player = players
+                    .iter()
+                    .find(|player| player.user.steam_id == SteamID::from(76561197998883586))
+                    .expect("player not found");
+
+                // note that these include post-game kills so the exact values might need to change if the parser ever starts excluding those
+                assert_eq(reconnected_player.kills, 26)?;
+                assert_eq(reconnected_player.assists, 22)?;
+                assert_eq(reconnected_player.deaths, 14)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list reconnected player filter", |client| async move {
-            let list = client
-                .list(
-                    ListParams::default().with_players(vec![
-                        76561198024494988,
-                        76561198011495003,
-                        76561197998883586, // <- reconnected
-                    ]),
-                    1,
-                )
-                .await?;
-            assert_eq(list.len(), 1)?;
-            assert_eq(list[0].id, 4)?;
-
-            let players = list[0].get_players(client).await?;
-            let reconnected_player = players
-                .iter()
-                .find(|player| player.user.steam_id == SteamID::from(76561197998883586))
-                .expect("player not found");
-
-            // note that these include post-game kills so the exact values might need to change if the parser ever starts excluding those
-            assert_eq(reconnected_player.kills, 26)?;
-            assert_eq(reconnected_player.assists, 22)?;
-            assert_eq(reconnected_player.deaths, 14)?;
-            Ok(())
-        })
-        .await?;
+            test.step("list unknown player filter", |client| async move {
+                let list = client
+                    .list(
+                        ListParams::default().with_players(vec![76561197992320000]),
+                        1,
+                    )
+                    .await?;
+                assert_eq(list.len(), 0)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list unknown player filter", |client| async move {
-            let list = client
-                .list(
-                    ListParams::default().with_players(vec![76561197992320000]),
-                    1,
-                )
-                .await?;
-            assert_eq(list.len(), 0)?;
-            Ok(())
-        })
-        .await?;
+            test.step("list player and map filter", |client| async move {
+                let list = client
+                    .list(
+                        ListParams::default()
+                            .with_players(vec![76561197992327511])
+                            .with_map("cp_granary_pro"),
+                        1,
+                    )
+                    .await?;
+                assert_eq(list.len(), 1)?;
+                assert_eq(list[0].id, 1)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list player and map filter", |client| async move {
-            let list = client
-                .list(
-                    ListParams::default()
-                        .with_players(vec![76561197992327511])
-                        .with_map("cp_granary_pro"),
-                    1,
-                )
+            let first_time = test
+                .step("list type filter", |client| async move {
+                    let list = client
+                        .list(ListParams::default().with_type(GameType::Fours), 1)
+                        .await?;
+                    assert_eq(list.len(), 1)?;
+                    assert_eq(list[0].id, 3)?;
+                    Ok(list[0].time)
+                })
                 .await?;
-            assert_eq(list.len(), 1)?;
-            assert_eq(list[0].id, 1)?;
-            Ok(())
-        })
-        .await?;
 
-        let first_time = test
-            .step("list type filter", |client| async move {
+            test.step("list time filter after", |client| async move {
                 let list = client
-                    .list(ListParams::default().with_type(GameType::Fours), 1)
+                    .list(ListParams::default().with_after(first_time), 1)
                     .await?;
-                assert_eq(list.len(), 1)?;
-                assert_eq(list[0].id, 3)?;
-                Ok(list[0].time)
+
+                assert_eq(list.len(), 2)?;
+                assert_eq(list[0].id, 5)?;
+                assert_eq(list[1].id, 4)?;
+                Ok(())
             })
             .await?;
 
-        test.step("list time filter after", |client| async move {
-            let list = client
-                .list(ListParams::default().with_after(first_time), 1)
-                .await?;
+            test.step("list time filter before", |client| async move {
+                let list = client
+                    .list(ListParams::default().with_before(first_time), 1)
+                    .await?;
 
-            assert_eq(list.len(), 2)?;
-            assert_eq(list[0].id, 5)?;
-            assert_eq(list[1].id, 4)?;
-            Ok(())
-        })
-        .await?;
+                assert_eq(list.len(), 2)?;
+                assert_eq(list[0].id, 2)?;
+                assert_eq(list[1].id, 1)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list time filter before", |client| async move {
-            let list = client
-                .list(ListParams::default().with_before(first_time), 1)
-                .await?;
+            test.step("list time filter after boundary", |client| async move {
+                let list = client
+                    .list(ListParams::default().with_after(start), 1)
+                    .await?;
 
-            assert_eq(list.len(), 2)?;
-            assert_eq(list[0].id, 2)?;
-            assert_eq(list[1].id, 1)?;
-            Ok(())
-        })
-        .await?;
+                assert_eq(list.len(), 3)?;
+                assert_eq(list[0].id, 5)?;
+                assert_eq(list[1].id, 4)?;
+                assert_eq(list[2].id, 3)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list id filter after", |client| async move {
-            let list = client
-                .list(ListParams::default().with_after_id(3), 1)
-                .await?;
+            test.step("list time filter before boundary", |client| async move {
+                let list = client
+                    .list(
+                        ListParams::default().with_before(start + Duration::minutes(2)),
+                        1,
+                    )
+                    .await?;
 
-            assert_eq(list.len(), 2)?;
-            assert_eq(list[0].id, 5)?;
-            assert_eq(list[1].id, 4)?;
-            Ok(())
-        })
-        .await?;
+                assert_eq(list.len(), 3)?;
+                assert_eq(list[0].id, 3)?;
+                assert_eq(list[1].id, 2)?;
+                assert_eq(list[2].id, 1)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list id filter before", |client| async move {
-            let list = client
-                .list(ListParams::default().with_before_id(3), 1)
-                .await?;
+            test.step("list id filter after", |client| async move {
+                let list = client
+                    .list(ListParams::default().with_after_id(3), 1)
+                    .await?;
 
-            assert_eq(list.len(), 2)?;
-            assert_eq(list[0].id, 2)?;
-            assert_eq(list[1].id, 1)?;
-            Ok(())
-        })
-        .await?;
+                assert_eq(list.len(), 2)?;
+                assert_eq(list[0].id, 5)?;
+                assert_eq(list[1].id, 4)?;
+                Ok(())
+            })
+            .await?;
 
-        test.step("list by uploader", |client| async move {
-            let list = client
-                .list_uploads(
-                    SteamID::from(76561197992327511),
-                    ListParams::default().with_order(ListOrder::Ascending),
-                    1,
-                )
-                .await?;
-            assert_eq(list.len(), 0)?;
+            test.step("list id filter before", |client| async move {
+                let list = client
+                    .list(ListParams::default().with_before_id(3), 1)
+                    .await?;
 
-            let list = client
-                .list_uploads(
-                    SteamID::from(76561198024494988),
-                    ListParams::default().with_order(ListOrder::Ascending),
-                    1,
-                )
-                .await?;
-            assert_eq(list.len(), 5)?;
-            assert_eq(list[0].id, 1)?;
-            Ok(())
-        })
-        .await?;
+                assert_eq(list.len(), 2)?;
+                assert_eq(list[0].id, 2)?;
+                assert_eq(list[1].id, 1)?;
+                Ok(())
+            })
+            .await?;
 
-        Ok(())
-    })
+            test.step("list by uploader", |client| async move {
+                let list = client
+                    .list_uploads(
+                        SteamID::from(76561197992327511),
+                        ListParams::default().with_order(ListOrder::Ascending),
+                        1,
+                    )
+                    .await?;
+                assert_eq(list.len(), 0)?;
+
+                let list = client
+                    .list_uploads(
+                        SteamID::from(76561198024494988),
+                        ListParams::default().with_order(ListOrder::Ascending),
+                        1,
+                    )
+                    .await?;
+                assert_eq(list.len(), 5)?;
+                assert_eq(list[0].id, 1)?;
+                Ok(())
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
     .await;
 
-    success &= Test::run("Set url", &harness, |test| async move {
+    success &= Test::run("Set url", &[Tag::Auth], harness, |test| async move {
         let id = test
             .step("upload", |client| async move {
                 Ok(client
@@ -580,221 +714,104 @@ async fn main() -> Result<()> {
     })
     .await;
 
-    success &= Test::run("Private demos", &harness, |test| async move {
-        let id = test
-            .step("upload", |client| async move {
-                Ok(client
-                    .upload_private_demo(
-                        String::from("test.dem"),
-                        granary_data.to_vec(),
-                        String::from("RED"),
-                        String::from("BLUE"),
-                        String::from("token"),
-                    )
-                    .await?)
-            })
-            .await?;
-
-        assert_eq(id, 1)?;
-
-        test.step("get without key", |client| async move {
-            let demo = client.get(id).await?;
-            assert_object_eq!(demo => {
-                id == 1,
-                name == "test.dem",
-                map == "cp_granary_pro_rc8",
-                red_score == 0,
-                blue_score == 1,
-                player_count == 12,
-                url == "",
-                backend == "",
-            });
-
-            Ok(())
-        })
-        .await?;
-
-        test.step("get with key", |client| async move {
-            let mut client = client.clone();
-            client.set_access_key(access_key.clone());
-
-            let demo = client.get(id).await?;
-            assert_object_eq!(demo => {
-                id == 1,
-                name == "test.dem",
-                map == "cp_granary_pro_rc8",
-                red_score == 0,
-                blue_score == 1,
-                player_count == 12,
-                url == "https://localhost/ec/68/ec681d1b4846a7e3cb2c129fcbd858ac_test.dem",
-                backend == "static",
-            });
-
-            Ok(())
-        })
-        .await?;
+    success &= Test::run(
+        "Private demos",
+        &[Tag::Private, Tag::Auth],
+        harness,
+        |test| async move {
+            let id = test
+                .step("upload", |client| async move {
+                    Ok(client
+                        .upload_private_demo(
+                            String::from("test.dem"),
+                            granary_data.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from("token"),
+                        )
+                        .await?)
+                })
+                .await?;
 
-        test.step("get with wrong key", |client| async move {
-            let mut client = client.clone();
-            client.set_access_key("wrong".into());
+            assert_eq(id, 1)?;
 
-            let demo = client.get(id).await?;
-            assert_object_eq!(demo => {
-                id == 1,
-                name == "test.dem",
-                map == "cp_granary_pro_rc8",
-                red_score == 0,
-                blue_score == 1,
-                player_count == 12,
-                url == "",
-                backend == "",
-            });
+            test.step("get without key", |client| async move {
+                let demo = client.get(id).await?;
+                assert_object_eq!(demo => {
+                    id == 1,
+                    name == "test.dem",
+                    map == "cp_granary_pro_rc8",
+                    red_score == 0,
+                    blue_score == 1,
+                    player_count == 12,
+                    url == "",
+                    backend == "",
+                });
 
-            Ok(())
-        })
-        .await?;
+                Ok(())
+            })
+            .await?;
 
-        Ok(())
-    })
-    .await;
+            test.step("get with key", |client| async move {
+                let mut client = client.clone();
+                client.set_access_key(access_key.clone());
 
-    if !success {
-        std::process::exit(1);
-    }
+                let demo = client.get(id).await?;
+                assert_object_eq!(demo => {
+                    id == 1,
+                    name == "test.dem",
+                    map == "cp_granary_pro_rc8",
+                    red_score == 0,
+                    blue_score == 1,
+                    player_count == 12,
+                    url == "https://localhost/ec/68/ec681d1b4846a7e3cb2c129fcbd858ac_test.dem",
+                    backend == "static",
+                });
 
-    Ok(())
-}
+                Ok(())
+            })
+            .await?;
 
-fn verify_demo(api_result: &Demo, header: &Header, state: &MatchState) -> Result<()> {
-    use tf_demo_parser::demo::parser::gamestateanalyser;
+            test.step("get with wrong key", |client| async move {
+                let mut client = client.clone();
+                client.set_access_key("wrong".into());
 
-    fn map_team(team: Team) -> gamestateanalyser::Team {
-        match team {
-            Team::Red => gamestateanalyser::Team::Red,
-            Team::Blue => gamestateanalyser::Team::Blue,
-        }
-    }
+                let demo = client.get(id).await?;
+                assert_object_eq!(demo => {
+                    id == 1,
+                    name == "test.dem",
+                    map == "cp_granary_pro_rc8",
+                    red_score == 0,
+                    blue_score == 1,
+                    player_count == 12,
+                    url == "",
+                    backend == "",
+                });
 
-    fn map_class(class: Class) -> gamestateanalyser::Class {
-        match class {
-            Class::Scout => gamestateanalyser::Class::Scout,
-            Class::Soldier => gamestateanalyser::Class::Soldier,
-            Class::Pyro => gamestateanalyser::Class::Pyro,
-            Class::Demoman => gamestateanalyser::Class::Demoman,
-            Class::HeavyWeapons => gamestateanalyser::Class::Heavy,
-            Class::Medic => gamestateanalyser::Class::Medic,
-            Class::Engineer => gamestateanalyser::Class::Engineer,
-            Class::Sniper => gamestateanalyser::Class::Sniper,
-            Class::Spy => gamestateanalyser::Class::Spy,
-        }
-    }
+                Ok(())
+            })
+            .await?;
 
-    assert_eq(&api_result.map, &header.map).wrap_err("Failed to compare map")?;
-    assert_eq(
-        api_result.red_score,
-        state
-            .rounds
-            .iter()
-            .filter(|round| round.winner == gamestateanalyser::Team::Red)
-            .count() as u8,
-    )
-    .wrap_err("Failed to compare red score")?;
-    assert_eq(
-        api_result.blue_score,
-        state
-            .rounds
-            .iter()
-            .filter(|round| round.winner == gamestateanalyser::Team::Blue)
-            .count() as u8,
+            Ok(())
+        },
     )
-    .wrap_err("Failed to compare blue score")?;
-    assert_eq(&api_result.server, &header.server).wrap_err("Failed to compare server")?;
-    assert_eq(&api_result.nick, &header.nick).wrap_err("Failed to compare server")?;
-    assert_eq(api_result.duration, header.duration as u16).wrap_err("Failed to compare server")?;
-
-    let mut players = state
-        .users
-        .values()
-        .filter(|user| user.team.is_player())
-        .collect::<Vec<_>>();
-
-    players.sort_by(|a, b| {
-        SteamID::try_from(a.steam_id.as_str())
-            .unwrap()
-            .account_id()
-            .cmp(&SteamID::try_from(b.steam_id.as_str()).unwrap().account_id())
-    });
-
-    let mut api_players = api_result.players.clone().unwrap();
-    api_players.sort_by(|a, b| {
-        a.user
-            .steam_id
-            .account_id()
-            .cmp(&b.user.steam_id.account_id())
-    });
-
-    assert_eq(api_result.player_count, players.len() as u8)
-        .wrap_err("Failed to compare player count")?;
-    assert_eq(api_players.len(), players.len()).wrap_err("Failed to compare player count")?;
-
-    for (api_player, player) in api_players.iter().zip(players.iter()) {
-        assert_eq(&api_player.user.name, &player.name).wrap_err_with(|| {
-            format!("Failed to compare player name for {}", api_player.user.name)
-        })?;
-        assert_eq(
-            &api_player.user.steam_id,
-            &SteamID::try_from(player.steam_id.as_str()).unwrap(),
-        )
-        .wrap_err_with(|| format!("Failed to compare steam id for {}", api_player.user.name))?;
-        assert_eq(map_team(api_player.team), player.team)
-            .wrap_err_with(|| format!("Failed to compare team for {}", api_player.user.name))?;
-        assert_eq(
-            map_class(api_player.class),
-            player.classes.sorted().next().unwrap().0,
-        )
-        .wrap_err_with(|| format!("Failed to compare class for {}", api_player.user.name))?;
-        let kills = state
-            .deaths
-            .iter()
-            .filter(|kill| kill.killer == player.user_id)
-            .count() as u8;
-        let assists = state
-            .deaths
-            .iter()
-            .filter(|kill| kill.assister == Some(player.user_id))
-            .count() as u8;
-        let deaths = state
-            .deaths
-            .iter()
-            .filter(|kill| kill.victim == player.user_id)
-            .count() as u8;
-        assert_eq(api_player.kills, kills)
-            .wrap_err_with(|| format!("Failed to compare kills for {}", api_player.user.name))?;
-        assert_eq(api_player.assists, assists)
-            .wrap_err_with(|| format!("Failed to compare assists for {}", api_player.user.name))?;
-        assert_eq(api_player.deaths, deaths)
-            .wrap_err_with(|| format!("Failed to compare deaths for {}", api_player.user.name))?;
-    }
-
-    Ok(())
-}
-
-fn verify_chat(chat: &[ChatMessage], state: &MatchState) -> Result<()> {
-    assert_eq(chat.len(), state.chat.len())
-        .wrap_err("Failed to compare number of chat messages")?;
-
-    let mut demo_chat = state.chat.clone();
-    demo_chat.sort_by(|a, b| a.tick.cmp(&b.tick));
-
-    for (api_chat, chat) in chat.iter().zip(demo_chat.iter()) {
-        assert_eq(&api_chat.message, &chat.text).wrap_err("Failed to compare chat message")?;
-        assert_eq(&api_chat.user, &chat.from).wrap_err("Failed to compare chat message sender")?;
-        // assert_eq(
-        //     api_chat.time,
-        //     (chat.tick as f32 * state.interval_per_tick) as u32,
-        // )
-        // .wrap_err("Failed to compare chat message time")?;
-    }
+    .await;
 
-    Ok(())
+    success &= suites::privacy::run(harness, access_key).await;
+    success &= suites::dedup::run(harness, edit_key).await;
+    success &= suites::blacklist::run(harness).await;
+    success &= suites::storage::run(harness).await;
+    success &= suites::download::run(harness).await;
+    success &= suites::contract::run(harness, edit_key).await;
+    success &= suites::pagination::run(harness).await;
+    success &= suites::filters::run(harness).await;
+    success &= suites::users::run(harness).await;
+    success &= suites::teams::run(harness).await;
+    success &= suites::storage_keys::run(harness).await;
+    success &= suites::concurrency::run(harness).await;
+    success &= suites::security::run(harness, edit_key).await;
+    success &= suites::auth::run(harness, edit_key, access_key).await;
+    success &= suites::timing::run(harness, edit_key, access_key).await;
+
+    Ok(success)
 }
@@ -0,0 +1,334 @@
+//! Property based testing of list filters
+//!
+//! Uploads the fixture demos, then compares the results of randomly generated filter combinations
+//! against the results expected from a local model of the fixtures. Failing combinations are
+//! shrunk to a minimal failing filter.
+
+use crate::env::env_or;
+use crate::fixtures;
+use crate::format::Format;
+use crate::harness::Harness;
+use crate::verify::parse_demo;
+use color_eyre::{eyre::WrapErr, Result};
+use colored::Colorize;
+use demostf_client::{ApiClient, ListOrder, ListParams, SteamID};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::convert::TryFrom;
+use std::time::{SystemTime, UNIX_EPOCH};
+use time::{Duration, OffsetDateTime};
+
+const UNKNOWN_PLAYER: u64 = 76561197992320000;
+
+/// A demo as the api should know it, based on parsing the fixture
+struct ModelDemo {
+    id: u32,
+    map: String,
+    players: Vec<u64>,
+    player_count: usize,
+    time: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Filter {
+    map: Option<String>,
+    players: Vec<u64>,
+    format: Option<Format>,
+    before: Option<OffsetDateTime>,
+    after: Option<OffsetDateTime>,
+    before_id: Option<u64>,
+    after_id: Option<u64>,
+    ascending: bool,
+}
+
+impl Filter {
+    fn random(rng: &mut StdRng, model: &[ModelDemo]) -> Self {
+        let maps: Vec<&str> = model.iter().map(|demo| demo.map.as_str()).collect();
+        let mut players: Vec<u64> = model
+            .iter()
+            .flat_map(|demo| demo.players.iter().copied())
+            .collect();
+        players.push(UNKNOWN_PLAYER);
+        let times: Vec<OffsetDateTime> = model.iter().map(|demo| demo.time).collect();
+
+        let random_time = |rng: &mut StdRng| {
+            times
+                .choose(rng)
+                .map(|time| *time + Duration::seconds(rng.gen_range(-1..=1)))
+        };
+
+        Filter {
+            map: match rng.gen_range(0..4) {
+                0 => maps.choose(rng).map(|map| map.to_string()),
+                1 => maps
+                    .choose(rng)
+                    .map(|map| map[..rng.gen_range(1..=map.len())].to_string()),
+                2 => Some(String::from("pl_unknown")),
+                _ => None,
+            },
+            players: (0..rng.gen_range(0..=3))
+                .filter_map(|_| players.choose(rng).copied())
+                .collect(),
+            format: if rng.gen_bool(0.3) {
+                Format::ALL.choose(rng).copied()
+            } else {
+                None
+            },
+            before: if rng.gen_bool(0.3) {
+                random_time(rng)
+            } else {
+                None
+            },
+            after: if rng.gen_bool(0.3) {
+                random_time(rng)
+            } else {
+                None
+            },
+            before_id: if rng.gen_bool(0.3) {
+                Some(rng.gen_range(0..=model.len() as u64 + 1))
+            } else {
+                None
+            },
+            after_id: if rng.gen_bool(0.3) {
+                Some(rng.gen_range(0..=model.len() as u64 + 1))
+            } else {
+                None
+            },
+            ascending: rng.gen_bool(0.5),
+        }
+    }
+
+    fn params(&self) -> ListParams {
+        let mut params = ListParams::default().with_order(if self.ascending {
+            ListOrder::Ascending
+        } else {
+            ListOrder::Descending
+        });
+        if let Some(map) = &self.map {
+            params = params.with_map(map.as_str());
+        }
+        if !self.players.is_empty() {
+            params = params.with_players(self.players.clone());
+        }
+        if let Some(format) = self.format {
+            params = params.with_type(format.game_type());
+        }
+        if let Some(before) = self.before {
+            params = params.with_before(before);
+        }
+        if let Some(after) = self.after {
+            params = params.with_after(after);
+        }
+        if let Some(before_id) = self.before_id {
+            params = params.with_before_id(before_id);
+        }
+        if let Some(after_id) = self.after_id {
+            params = params.with_after_id(after_id);
+        }
+        params
+    }
+
+    fn matches(&self, demo: &ModelDemo) -> bool {
+        if let Some(map) = &self.map {
+            if !demo.map.starts_with(map.as_str()) {
+                return false;
+            }
+        }
+        if let Some(format) = self.format {
+            if format.player_count() != demo.player_count {
+                return false;
+            }
+        }
+        if matches!(self.before, Some(before) if demo.time >= before)
+            || matches!(self.after, Some(after) if demo.time <= after)
+            || matches!(self.before_id, Some(before_id) if demo.id as u64 >= before_id)
+            || matches!(self.after_id, Some(after_id) if demo.id as u64 <= after_id)
+        {
+            return false;
+        }
+        self.players
+            .iter()
+            .all(|player| demo.players.contains(player))
+    }
+
+    fn expected(&self, model: &[ModelDemo]) -> Vec<u32> {
+        let mut ids: Vec<u32> = model
+            .iter()
+            .filter(|demo| self.matches(demo))
+            .map(|demo| demo.id)
+            .collect();
+        ids.sort_unstable();
+        if !self.ascending {
+            ids.reverse();
+        }
+        ids
+    }
+
+    /// Filters that are one step simpler than this one
+    fn simplifications(&self) -> Vec<Filter> {
+        let mut simpler = Vec::new();
+        if self.map.is_some() {
+            simpler.push(Filter {
+                map: None,
+                ..self.clone()
+            });
+        }
+        for i in 0..self.players.len() {
+            let mut filter = self.clone();
+            filter.players.remove(i);
+            simpler.push(filter);
+        }
+        if self.format.is_some() {
+            simpler.push(Filter {
+                format: None,
+                ..self.clone()
+            });
+        }
+        if self.before.is_some() {
+            simpler.push(Filter {
+                before: None,
+                ..self.clone()
+            });
+        }
+        if self.after.is_some() {
+            simpler.push(Filter {
+                after: None,
+                ..self.clone()
+            });
+        }
+        if self.before_id.is_some() {
+            simpler.push(Filter {
+                before_id: None,
+                ..self.clone()
+            });
+        }
+        if self.after_id.is_some() {
+            simpler.push(Filter {
+                after_id: None,
+                ..self.clone()
+            });
+        }
+        if self.ascending {
+            simpler.push(Filter {
+                ascending: false,
+                ..self.clone()
+            });
+        }
+        simpler
+    }
+}
+
+/// Upload all fixtures and build the model of what the api should contain
+///
+/// The upload times are spread out so the time filters have something to distinguish
+async fn seed_model(harness: &Harness, client: &ApiClient) -> Result<Vec<ModelDemo>> {
+    let start = OffsetDateTime::from_unix_timestamp(1_600_000_000)?;
+    let mut model = Vec::with_capacity(fixtures::ALL.len());
+    for (name, data) in fixtures::ALL {
+        let (header, state) = parse_demo(data).wrap_err_with(|| format!("Parsing {}", name))?;
+        let id = client
+            .upload_demo(
+                String::from(*name),
+                data.to_vec(),
+                String::from("RED"),
+                String::from("BLUE"),
+                String::from("token"),
+            )
+            .await?;
+        let time = start + Duration::minutes(model.len() as i64);
+        harness.set_upload_time(id, time).await?;
+
+        let players = state
+            .users
+            .values()
+            .filter(|user| user.team.is_player())
+            .map(|user| SteamID::try_from(user.steam_id.as_str()).map(u64::from))
+            .collect::<Result<Vec<u64>, _>>()?;
+
+        model.push(ModelDemo {
+            id,
+            map: header.map,
+            player_count: players.len(),
+            players,
+            time,
+        });
+    }
+    Ok(model)
+}
+
+/// Returns the actual result if it differs from the expected result
+async fn check(
+    client: &ApiClient,
+    model: &[ModelDemo],
+    filter: &Filter,
+) -> Result<Option<Vec<u32>>> {
+    let actual: Vec<u32> = client
+        .list(filter.params(), 1)
+        .await?
+        .iter()
+        .map(|demo| demo.id)
+        .collect();
+    if actual == filter.expected(model) {
+        Ok(None)
+    } else {
+        Ok(Some(actual))
+    }
+}
+
+async fn shrink(client: &ApiClient, model: &[ModelDemo], mut filter: Filter) -> Result<Filter> {
+    'outer: loop {
+        for candidate in filter.simplifications() {
+            if check(client, model, &candidate).await?.is_some() {
+                filter = candidate;
+                continue 'outer;
+            }
+        }
+        return Ok(filter);
+    }
+}
+
+/// Run the property tests, returns the number of failing cases
+pub async fn run(harness: &Harness) -> Result<u64> {
+    let cases: u64 = env_or("PROPERTY_CASES", 200)?;
+    let seed = env_or(
+        "PROPERTY_SEED",
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+    )?;
+    let mut rng = StdRng::seed_from_u64(seed);
+    let client = harness.client();
+
+    println!(" - List filter properties with seed {}", seed);
+
+    harness.reset().await?;
+    let model = seed_model(harness, &client).await?;
+    let mut failures = 0;
+
+    for case in 0..cases {
+        let filter = Filter::random(&mut rng, &model);
+        if check(&client, &model, &filter).await?.is_some() {
+            failures += 1;
+            let minimal = shrink(&client, &model, filter).await?;
+            let actual = check(&client, &model, &minimal).await?.unwrap_or_default();
+            println!(
+                "    - {}: {:?}\n      expected {:?}, got {:?}",
+                format!("case {}", case).red(),
+                minimal,
+                minimal.expected(&model),
+                actual
+            );
+        }
+    }
+
+    println!(
+        " - {} cases checked, {} failures",
+        cases,
+        if failures > 0 {
+            failures.to_string().red()
+        } else {
+            failures.to_string().green()
+        }
+    );
+
+    Ok(failures)
+}
@@ -0,0 +1,187 @@
+//! Plain http access to the api, for asserting the exact responses that the typed `ApiClient` hides
+
+use color_eyre::{eyre::WrapErr, Report, Result};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+#[derive(Clone)]
+pub struct RawClient {
+    client: Client,
+    base_url: String,
+    in_flight: InFlight,
+}
+
+/// Method, url and start time of a request, by request id
+type Requests = BTreeMap<u64, (Method, String, Instant)>;
+
+/// Requests that are currently being sent by the client, to report what a hanging step is waiting on
+#[derive(Clone, Default)]
+pub struct InFlight {
+    next_id: Arc<AtomicU64>,
+    requests: Arc<Mutex<Requests>>,
+}
+
+impl InFlight {
+    fn start(&self, method: Method, url: String) -> InFlightGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.requests
+            .lock()
+            .unwrap()
+            .insert(id, (method, url, Instant::now()));
+        InFlightGuard {
+            in_flight: self.clone(),
+            id,
+        }
+    }
+
+    /// Method, url and start time of every request in flight
+    pub fn requests(&self) -> Vec<(Method, String, Instant)> {
+        self.requests.lock().unwrap().values().cloned().collect()
+    }
+}
+
+struct InFlightGuard {
+    in_flight: InFlight,
+    id: u64,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.in_flight.requests.lock().unwrap().remove(&self.id);
+    }
+}
+
+pub struct RawResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl RawClient {
+    pub fn new(base_url: &str) -> Self {
+        RawClient {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').into(),
+            in_flight: InFlight::default(),
+        }
+    }
+
+    pub fn in_flight(&self) -> InFlight {
+        self.in_flight.clone()
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<RawResponse> {
+        let request = request.build()?;
+        let _guard = self
+            .in_flight
+            .start(request.method().clone(), request.url().to_string());
+        RawResponse::read(self.client.execute(request).await?).await
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    pub async fn get(&self, path: &str) -> Result<RawResponse> {
+        self.send(self.client.get(self.url(path))).await
+    }
+
+    pub async fn get_query(&self, path: &str, query: &[(&str, &str)]) -> Result<RawResponse> {
+        self.send(self.client.get(self.url(path)).query(query))
+            .await
+    }
+
+    pub async fn get_with_header(
+        &self,
+        path: &str,
+        name: &'static str,
+        value: HeaderValue,
+    ) -> Result<RawResponse> {
+        self.send(self.client.get(self.url(path)).header(name, value))
+            .await
+    }
+
+    pub async fn post_form(&self, path: &str, form: &[(&str, &str)]) -> Result<RawResponse> {
+        self.send(self.client.post(self.url(path)).form(form)).await
+    }
+
+    pub async fn upload(
+        &self,
+        name: &str,
+        data: &[u8],
+        red: &str,
+        blue: &str,
+        key: &str,
+    ) -> Result<RawResponse> {
+        let form = Form::new()
+            .text("name", name.to_string())
+            .text("red", red.to_string())
+            .text("blue", blue.to_string())
+            .text("key", key.to_string())
+            .part(
+                "demo",
+                Part::bytes(data.to_vec()).file_name(name.to_string()),
+            );
+        self.send(self.client.post(self.url("upload")).multipart(form))
+            .await
+    }
+}
+
+impl RawResponse {
+    async fn read(response: reqwest::Response) -> Result<Self> {
+        Ok(RawResponse {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.text().await?,
+        })
+    }
+
+    pub fn expect_status(&self, status: StatusCode) -> Result<()> {
+        if self.status == status {
+            Ok(())
+        } else {
+            Err(Report::msg(format!(
+                "Expected status {}, got {} with body {:?}",
+                status, self.status, self.body
+            )))
+        }
+    }
+
+    /// Responses shouldn't leak details of the php backend
+    pub fn expect_no_leak(&self) -> Result<()> {
+        for leak in [
+            "Stack trace",
+            "Fatal error",
+            "Exception",
+            "SQLSTATE",
+            ".php",
+        ] {
+            if self.body.contains(leak) {
+                return Err(Report::msg(format!(
+                    "Response leaks backend details: {:?}",
+                    self.body
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn header(&self, name: &str) -> Result<&str> {
+        let value = self
+            .headers
+            .get(name)
+            .ok_or_else(|| Report::msg(format!("Missing {} header", name)))?;
+        Ok(value.to_str()?)
+    }
+
+    pub fn json(&self) -> Result<Value> {
+        serde_json::from_str(&self.body)
+            .wrap_err_with(|| format!("Invalid json response {:?}", self.body))
+    }
+}
@@ -1,65 +1,181 @@
+use crate::baseline::Timings;
+use crate::flaky::{StepOutcome, StepResults};
+use crate::hang::HangDump;
 use crate::harness::Harness;
+use crate::tags::Tag;
 use color_eyre::{Report, Result};
 use colored::Colorize;
 use demostf_client::ApiClient;
 use std::fmt::Debug;
 use std::future::Future;
+use std::time::{Duration, Instant};
+
+macro_rules! assert_object_eq {
+    ($obj:expr => { $($name:ident == $value:expr),* }) => {
+        $($crate::report::assert_eq_borrow(&$obj.$name, $value)?;)*
+    };
+    ($obj:expr => { $($name:ident == $value:expr),* , }) => {
+        $($crate::report::assert_eq_borrow(&$obj.$name, $value)?;)*
+    };
+}
 
 #[derive(Clone)]
 pub struct Test {
     client: ApiClient,
+    name: String,
+    timings: Timings,
+    results: StepResults,
+    retries: u32,
+    timeout: Duration,
+    hang_dump: Option<HangDump>,
 }
 
 impl Test {
-    pub async fn run<'a, Fut: Future<Output = Result<()>> + 'a, F: FnOnce(Test) -> Fut + 'a>(
+    /// Run the test, repeated as often as configured, returns whether all runs passed
+    ///
+    /// Tests with tags that aren't selected are skipped and count as passed
+    pub async fn run<'a, Fut: Future<Output = Result<()>> + 'a, F: Fn(Test) -> Fut + 'a>(
         name: &str,
+        tags: &[Tag],
         harness: &'a Harness,
         f: F,
     ) -> bool {
-        println!(" - {}", name);
-
-        if let Err(e) = harness.reset().await {
-            println!("   {}: {:#}", "Reset api server".red(), e);
-            println!("      {}", "❌".red());
-            return false;
-        } else {
-            println!("    - {}", "Reset api server".green());
+        if !harness.tag_filter().matches(tags) {
+            println!(" - {}: {}", name, "skipped".yellow());
+            return true;
         }
 
-        let test = Test {
-            client: harness.client(),
-        };
+        let repeat = harness.repeat();
+        let mut success = true;
+
+        for run in 1..=repeat {
+            if repeat > 1 {
+                println!(" - {} ({}/{})", name, run, repeat);
+            } else {
+                println!(" - {}", name);
+            }
 
-        match f(test).await {
-            Ok(_) => {
-                println!("      {}", "✓".green());
-                true
+            if let Err(e) = harness.reset().await {
+                println!("   {}: {:#}", "Reset api server".red(), e);
+                println!("      {}", "❌".red());
+                success = false;
+                continue;
+            } else {
+                println!("    - {}", "Reset api server".green());
             }
-            Err(e) => {
-                println!("      {}: {:#}", "❌".red(), e);
-                false
+
+            let test = Test {
+                client: harness.client(),
+                name: String::from(name),
+                timings: harness.timings(),
+                results: harness.step_results(),
+                retries: harness.step_retries(),
+                timeout: harness.step_timeout(),
+                hang_dump: harness.hang_dump(),
+            };
+
+            let result = match f(test.clone()).await {
+                Ok(_) => {
+                    test.step("validate responses", |_| async move {
+                        harness.validate_responses().await
+                    })
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok(_) => {
+                    println!("      {}", "✓".green());
+                }
+                Err(e) => {
+                    println!("      {}: {:#}", "❌".red(), e);
+                    success = false;
+                }
             }
         }
+
+        success
     }
 
     pub async fn step<
         'a,
         T,
         Fut: Future<Output = Result<T>> + 'a,
-        F: FnOnce(&'a ApiClient) -> Fut + 'a,
+        F: Fn(&'a ApiClient) -> Fut + 'a,
     >(
         &'a self,
         name: &str,
         f: F,
     ) -> Result<T> {
-        match f(&self.client).await {
-            Ok(res) => {
-                println!("    - {}", name.green());
-                Ok(res)
+        self.step_with_timeout(name, self.timeout, f).await
+    }
+
+    /// Run a step that is expected to take longer than the default timeout
+    pub async fn step_with_timeout<
+        'a,
+        T,
+        Fut: Future<Output = Result<T>> + 'a,
+        F: Fn(&'a ApiClient) -> Fut + 'a,
+    >(
+        &'a self,
+        name: &str,
+        timeout: Duration,
+        f: F,
+    ) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            let start = Instant::now();
+            match self.with_timeout(name, timeout, f(&self.client)).await {
+                Ok(res) => {
+                    self.timings.record(&self.name, name, start.elapsed());
+                    if attempt == 0 {
+                        self.results.record(&self.name, name, StepOutcome::Passed);
+                        println!("    - {}", name.green());
+                    } else {
+                        self.results.record(&self.name, name, StepOutcome::Flaky);
+                        println!(
+                            "    - {}",
+                            format!("{} (flaky, passed after {} attempts)", name, attempt + 1)
+                                .yellow()
+                        );
+                    }
+                    return Ok(res);
+                }
+                Err(e) if attempt < self.retries => {
+                    println!("    - {}: {:#}, retrying", name.yellow(), e);
+                    attempt += 1;
+                }
+                Err(e) => {
+                    self.results.record(&self.name, name, StepOutcome::Failed);
+                    println!("    - {}: {:#}", name.red(), e);
+                    return Err(e);
+                }
             }
-            Err(e) => {
-                println!("    - {}: {:#}", name.red(), e);
-                Err(e)
+        }
+    }
+
+    /// Fail the step if it doesn't complete in time, dumping what it was waiting on while the
+    /// requests are still in flight
+    async fn with_timeout<T>(
+        &self,
+        name: &str,
+        timeout: Duration,
+        future: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let start = Instant::now();
+        tokio::pin!(future);
+        tokio::select! {
+            result = &mut future => result,
+            _ = tokio::time::sleep(timeout) => {
+                if let Some(hang_dump) = &self.hang_dump {
+                    println!("    - {} timed out while waiting on", name.red());
+                    hang_dump.print().await;
+                }
+                Err(Report::msg(format!(
+                    "Timed out after {:.1}s",
+                    start.elapsed().as_secs_f64()
+                )))
             }
         }
     }
@@ -86,3 +202,8 @@ pub fn assert_eq_borrow<A: Debug, B: PartialEq<A> + Debug>(a: &A, b: B) -> Resul
         )))
     }
 }
+
+/// Whether the error was caused by a 5xx response from the api
+pub fn is_server_error(error: &demostf_client::Error) -> bool {
+    matches!(error, demostf_client::Error::ServerError(_))
+}
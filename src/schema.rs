@@ -0,0 +1,172 @@
+//! Validation of api responses against the json schema in `schema/v1.json`
+//!
+//! Only the subset of json schema used by the schema file is supported: `type`, `enum`, `pattern`,
+//! `$ref`, `properties`, `required`, `additionalProperties` and `items`.
+
+use color_eyre::{Report, Result};
+use regex::Regex;
+use serde_json::Value;
+
+const SCHEMA: &str = include_str!("../schema/v1.json");
+
+pub struct Schema {
+    root: Value,
+}
+
+impl Schema {
+    pub fn load() -> Result<Self> {
+        Ok(Schema {
+            root: serde_json::from_str(SCHEMA)?,
+        })
+    }
+
+    /// Validate the value against one of the definitions from the schema, the error lists the json
+    /// pointer of every violation
+    pub fn validate(&self, definition: &str, value: &Value) -> Result<()> {
+        let schema = self.definition(definition)?;
+        let mut errors = Vec::new();
+        self.check(schema, value, "", &mut errors)?;
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Report::msg(format!(
+                "Response doesn't match schema {}:\n{}",
+                definition,
+                errors.join("\n")
+            )))
+        }
+    }
+
+    fn definition(&self, name: &str) -> Result<&Value> {
+        self.root
+            .get("definitions")
+            .and_then(|definitions| definitions.get(name))
+            .ok_or_else(|| Report::msg(format!("Unknown schema definition {}", name)))
+    }
+
+    fn check(
+        &self,
+        schema: &Value,
+        value: &Value,
+        pointer: &str,
+        errors: &mut Vec<String>,
+    ) -> Result<()> {
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            let name = reference
+                .strip_prefix("#/definitions/")
+                .ok_or_else(|| Report::msg(format!("Unsupported $ref {}", reference)))?;
+            return self.check(self.definition(name)?, value, pointer, errors);
+        }
+
+        if let Some(ty) = schema.get("type") {
+            let allowed: Vec<&str> = match ty {
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                _ => ty.as_str().into_iter().collect(),
+            };
+            if !allowed.iter().any(|ty| matches_type(ty, value)) {
+                errors.push(format!(
+                    "{}: expected {}, got {}",
+                    display_pointer(pointer),
+                    allowed.join(" or "),
+                    value
+                ));
+                return Ok(());
+            }
+        }
+
+        if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+            if !options.contains(value) {
+                errors.push(format!(
+                    "{}: expected one of {}, got {}",
+                    display_pointer(pointer),
+                    Value::Array(options.clone()),
+                    value
+                ));
+            }
+        }
+
+        if let (Value::String(string), Some(pattern)) =
+            (value, schema.get("pattern").and_then(Value::as_str))
+        {
+            if !Regex::new(pattern)?.is_match(string) {
+                errors.push(format!(
+                    "{}: expected a string matching {}, got {:?}",
+                    display_pointer(pointer),
+                    pattern,
+                    string
+                ));
+            }
+        }
+
+        if let Value::Object(object) = value {
+            let properties = schema.get("properties").and_then(Value::as_object);
+
+            for required in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                if !object.contains_key(required) {
+                    errors.push(format!(
+                        "{}: missing field",
+                        display_pointer(&child_pointer(pointer, required))
+                    ));
+                }
+            }
+
+            for (key, field) in object {
+                let field_pointer = child_pointer(pointer, key);
+                match properties.and_then(|properties| properties.get(key)) {
+                    Some(field_schema) => {
+                        self.check(field_schema, field, &field_pointer, errors)?
+                    }
+                    None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                        errors.push(format!("{}: unexpected field", field_pointer))
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+            for (index, item) in items.iter().enumerate() {
+                self.check(
+                    item_schema,
+                    item,
+                    &child_pointer(pointer, &index.to_string()),
+                    errors,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn matches_type(ty: &str, value: &Value) -> bool {
+    match ty {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+fn child_pointer(pointer: &str, key: &str) -> String {
+    format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"))
+}
+
+fn display_pointer(pointer: &str) -> &str {
+    if pointer.is_empty() {
+        "/"
+    } else {
+        pointer
+    }
+}
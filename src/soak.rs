@@ -0,0 +1,187 @@
+//! Soak testing
+//!
+//! Runs the full test suite in a loop for a long time, sampling the api latency and database
+//! statistics after every iteration. Metrics that keep growing, or a latency that drifts away from
+//! where it started, point to a leak in the api.
+
+use crate::env::{env_opt, env_or};
+use crate::harness::Harness;
+use color_eyre::Result;
+use colored::Colorize;
+use demostf_client::ListParams;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Number of requests used to measure the latency in a single sample
+const LATENCY_PROBES: usize = 10;
+
+struct SoakConfig {
+    duration: Duration,
+    /// Number of suite runs, if set the duration is ignored
+    iterations: Option<u64>,
+    /// Number of consecutive increasing samples before a metric is flagged
+    growth_samples: usize,
+    /// Factor by which the latency can increase before it's flagged
+    latency_drift: f64,
+}
+
+impl SoakConfig {
+    fn from_env() -> Result<Self> {
+        let duration = env_or("SOAK_DURATION", 60 * 60)?;
+        let iterations = env_opt("SOAK_ITERATIONS")?;
+        let growth_samples = env_or("SOAK_GROWTH_SAMPLES", 10)?;
+        let latency_drift = env_or("SOAK_LATENCY_DRIFT", 1.5)?;
+
+        Ok(SoakConfig {
+            duration: Duration::from_secs(duration),
+            iterations,
+            growth_samples,
+            latency_drift,
+        })
+    }
+
+    fn done(&self, iteration: u64, start: Instant) -> bool {
+        match self.iterations {
+            Some(iterations) => iteration >= iterations,
+            None => start.elapsed() >= self.duration,
+        }
+    }
+}
+
+/// Median time of a list request in milliseconds
+async fn sample_latency(harness: &Harness) -> Result<f64> {
+    let client = harness.client();
+    let mut latencies = Vec::with_capacity(LATENCY_PROBES);
+    for _ in 0..LATENCY_PROBES {
+        let start = Instant::now();
+        client.list(ListParams::default(), 1).await?;
+        latencies.push(start.elapsed().as_secs_f64() * 1000.0);
+    }
+    latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    Ok(latencies[LATENCY_PROBES / 2])
+}
+
+/// Take a sample of all metrics, by name
+async fn sample(harness: &Harness) -> Result<BTreeMap<String, f64>> {
+    let mut metrics = BTreeMap::new();
+    metrics.insert(String::from("latency (ms)"), sample_latency(harness).await?);
+    metrics.insert(
+        String::from("connections"),
+        harness.connection_count().await? as f64,
+    );
+    for (table, size, dead_tuples) in harness.table_stats().await? {
+        metrics.insert(format!("{} size (bytes)", table), size as f64);
+        metrics.insert(format!("{} dead tuples", table), dead_tuples as f64);
+    }
+    Ok(metrics)
+}
+
+/// Whether the last `count` samples are all higher than the one before them
+fn monotonic_growth(samples: &[f64], count: usize) -> bool {
+    samples.len() > count
+        && samples[samples.len() - count - 1..]
+            .windows(2)
+            .all(|pair| pair[1] > pair[0])
+}
+
+fn mean(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+/// Ratio between the mean of the last and first quarter of the samples
+fn drift(samples: &[f64]) -> Option<f64> {
+    let quarter = samples.len() / 4;
+    if quarter == 0 {
+        return None;
+    }
+    Some(mean(&samples[samples.len() - quarter..]) / mean(&samples[..quarter]))
+}
+
+/// Run the soak test, returns the number of flagged metrics
+pub async fn run(harness: &Harness) -> Result<u64> {
+    let config = SoakConfig::from_env()?;
+    let start = Instant::now();
+    let mut history: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    let mut failed_runs = 0;
+    let mut iteration = 0;
+
+    while !config.done(iteration, start) {
+        iteration += 1;
+        println!(" - Soak iteration {}", iteration);
+
+        if !crate::run_tests(harness).await? {
+            failed_runs += 1;
+        }
+
+        // sampled before the next run resets the database, so the tables still hold the data
+        // from the last test
+        let metrics = sample(harness).await?;
+        println!(
+            "    - {}",
+            format!(
+                "{} elapsed, latency {:.1}ms, {} connections",
+                humanize(start.elapsed()),
+                metrics["latency (ms)"],
+                metrics["connections"]
+            )
+            .green()
+        );
+        for (name, value) in metrics {
+            history.entry(name).or_default().push(value);
+        }
+    }
+
+    let mut flagged = 0;
+    for (name, samples) in &history {
+        if name == "latency (ms)" {
+            if let Some(drift) = drift(samples) {
+                if drift > config.latency_drift {
+                    flagged += 1;
+                    println!(
+                        " - {}: increased by a factor of {:.2} from {:.1}ms",
+                        name.red(),
+                        drift,
+                        samples[0]
+                    );
+                }
+            }
+        } else if monotonic_growth(samples, config.growth_samples) {
+            flagged += 1;
+            println!(
+                " - {}: grew for the last {} samples, from {} to {}",
+                name.red(),
+                config.growth_samples,
+                samples[samples.len() - config.growth_samples - 1],
+                samples[samples.len() - 1]
+            );
+        }
+    }
+
+    println!(
+        " - {} iterations in {}, {} failed runs, {} flagged metrics",
+        iteration,
+        humanize(start.elapsed()),
+        if failed_runs > 0 {
+            failed_runs.to_string().red()
+        } else {
+            failed_runs.to_string().green()
+        },
+        if flagged > 0 {
+            flagged.to_string().red()
+        } else {
+            flagged.to_string().green()
+        }
+    );
+
+    Ok(flagged + failed_runs)
+}
+
+fn humanize(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{}h{:02}m{:02}s",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
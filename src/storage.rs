@@ -0,0 +1,60 @@
+use color_eyre::{eyre::WrapErr, Report, Result};
+use std::path::PathBuf;
+
+/// Access to the demo files stored by the api, either directly from the demo root or over http
+pub enum DemoStorage {
+    Directory(PathBuf),
+    Http {
+        client: reqwest::Client,
+        base_url: String,
+    },
+}
+
+impl DemoStorage {
+    /// Storage configured by the `DEMO_ROOT` or `STATIC_URL` environment variables
+    pub fn from_env() -> Option<Self> {
+        if let Ok(root) = dotenv::var("DEMO_ROOT") {
+            Some(DemoStorage::Directory(root.into()))
+        } else if let Ok(base_url) = dotenv::var("STATIC_URL") {
+            Some(DemoStorage::Http {
+                client: reqwest::Client::new(),
+                base_url: base_url.trim_end_matches('/').into(),
+            })
+        } else {
+            None
+        }
+    }
+
+    pub async fn read(&self, path: &str) -> Result<Vec<u8>> {
+        match self {
+            DemoStorage::Directory(root) => {
+                let path = root.join(path);
+                tokio::fs::read(&path)
+                    .await
+                    .wrap_err_with(|| format!("Failed to read {}", path.display()))
+            }
+            DemoStorage::Http { client, base_url } => {
+                let url = format!("{}/{}", base_url, path);
+                let response = client.get(&url).send().await?;
+                if !response.status().is_success() {
+                    return Err(Report::msg(format!(
+                        "Failed to download {}: {}",
+                        url,
+                        response.status()
+                    )));
+                }
+                Ok(response.bytes().await?.to_vec())
+            }
+        }
+    }
+}
+
+pub fn hex(hash: &[u8; 16]) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Path of a stored demo relative to the demo root, `ab/cd/abcd..._name.dem`
+pub fn storage_path(hash: &[u8; 16], name: &str) -> String {
+    let hex = hex(hash);
+    format!("{}/{}/{}_{}", &hex[0..2], &hex[2..4], hex, name)
+}
@@ -0,0 +1,213 @@
+//! Authentication and authorization of every protected operation with every kind of credential
+//!
+//! The expected outcome of each combination is declared in `MATRIX`, a denied upload or set url
+//! should fail with `Error::InvalidApiKey` while denied access to a private demo hides the `url`
+//! and `backend`.
+
+use crate::fixtures::{self, GRANARY};
+use crate::harness::Harness;
+use crate::report::Test;
+use crate::suites::upload_private_fixture;
+use crate::tags::Tag;
+use color_eyre::{Report, Result};
+use demostf_client::ApiClient;
+use Credential::*;
+use Operation::*;
+use Outcome::*;
+
+const OTHER_STEAM_ID: u64 = 76561197992327511;
+const OTHER_TOKEN: &str = "other_token";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operation {
+    Upload,
+    PrivateUpload,
+    SetUrl,
+    PrivateAccess,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Credential {
+    UploaderToken,
+    OtherUserToken,
+    EditKey,
+    AccessKey,
+    Wrong,
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Allowed,
+    Denied,
+}
+
+const MATRIX: &[(Operation, Credential, Outcome)] = &[
+    (Upload, UploaderToken, Allowed),
+    (Upload, OtherUserToken, Allowed),
+    (Upload, EditKey, Denied),
+    (Upload, AccessKey, Denied),
+    (Upload, Wrong, Denied),
+    (Upload, Empty, Denied),
+    (PrivateUpload, UploaderToken, Allowed),
+    (PrivateUpload, OtherUserToken, Allowed),
+    (PrivateUpload, EditKey, Denied),
+    (PrivateUpload, AccessKey, Denied),
+    (PrivateUpload, Wrong, Denied),
+    (PrivateUpload, Empty, Denied),
+    (SetUrl, EditKey, Allowed),
+    (SetUrl, AccessKey, Denied),
+    (SetUrl, UploaderToken, Denied),
+    (SetUrl, OtherUserToken, Denied),
+    (SetUrl, Wrong, Denied),
+    (SetUrl, Empty, Denied),
+    (PrivateAccess, AccessKey, Allowed),
+    (PrivateAccess, UploaderToken, Allowed),
+    (PrivateAccess, OtherUserToken, Denied),
+    (PrivateAccess, EditKey, Denied),
+    (PrivateAccess, Wrong, Denied),
+    (PrivateAccess, Empty, Denied),
+];
+
+struct Keys<'a> {
+    edit: &'a str,
+    access: &'a str,
+}
+
+impl Credential {
+    fn value(self, keys: &Keys) -> String {
+        String::from(match self {
+            UploaderToken => "token",
+            OtherUserToken => OTHER_TOKEN,
+            EditKey => keys.edit,
+            AccessKey => keys.access,
+            Wrong => "wrong",
+            Empty => "",
+        })
+    }
+}
+
+/// Outcome of an operation that reports a denied credential with `Error::InvalidApiKey`
+fn outcome<T>(result: Result<T, demostf_client::Error>) -> Result<Outcome> {
+    match result {
+        Ok(_) => Ok(Allowed),
+        Err(demostf_client::Error::InvalidApiKey) => Ok(Denied),
+        Err(e) => Err(Report::msg(format!("Unexpected error: {}", e))),
+    }
+}
+
+async fn perform(
+    client: &ApiClient,
+    operation: Operation,
+    key: String,
+    index: usize,
+    id: u32,
+) -> Result<Outcome> {
+    match operation {
+        Upload => outcome(
+            client
+                .upload_demo(
+                    String::from("test.dem"),
+                    fixtures::unique(GRANARY, &format!("auth {}", index)),
+                    String::from("RED"),
+                    String::from("BLUE"),
+                    key,
+                )
+                .await,
+        ),
+        PrivateUpload => outcome(
+            client
+                .upload_private_demo(
+                    String::from("test.dem"),
+                    fixtures::unique(GRANARY, &format!("auth {}", index)),
+                    String::from("RED"),
+                    String::from("BLUE"),
+                    key,
+                )
+                .await,
+        ),
+        SetUrl => {
+            let demo = client.get(id).await?;
+            outcome(
+                client
+                    .set_url(
+                        id,
+                        "example",
+                        "somedemo.dem",
+                        "https://example.com/somedemo.dem",
+                        demo.hash,
+                        &key,
+                    )
+                    .await,
+            )
+        }
+        PrivateAccess => {
+            let mut client = client.clone();
+            client.set_access_key(key);
+            let demo = client.get(id).await?;
+            match (demo.url.as_str(), demo.backend.as_str()) {
+                ("", "") => Ok(Denied),
+                ("", _) | (_, "") => Err(Report::msg(format!(
+                    "Only partially redacted: url {:?}, backend {:?}",
+                    demo.url, demo.backend
+                ))),
+                _ => Ok(Allowed),
+            }
+        }
+    }
+}
+
+pub async fn run(harness: &Harness, edit_key: &str, access_key: &str) -> bool {
+    let keys = &Keys {
+        edit: edit_key,
+        access: access_key,
+    };
+    let mut success = true;
+
+    for operation in [Upload, PrivateUpload, SetUrl, PrivateAccess]
+        .iter()
+        .copied()
+    {
+        let name = format!("Authorization: {:?}", operation);
+        success &= Test::run(
+            &name,
+            &[Tag::Auth, Tag::Upload, Tag::Private, Tag::Destructive],
+            harness,
+            |test| async move {
+                harness
+                    .add_user(OTHER_STEAM_ID, "Other", OTHER_TOKEN)
+                    .await?;
+                let id = test
+                    .step("upload private", |client| async move {
+                        upload_private_fixture(client, "test.dem", GRANARY).await
+                    })
+                    .await?;
+
+                let cases = MATRIX
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, case)| case.0 == operation);
+                for (index, (_, credential, expected)) in cases {
+                    test.step(&format!("{:?}", credential), |client| async move {
+                        let key = credential.value(keys);
+                        let actual = perform(client, operation, key, index, id).await?;
+                        if actual == *expected {
+                            Ok(())
+                        } else {
+                            Err(Report::msg(format!(
+                                "Expected {:?}, got {:?}",
+                                expected, actual
+                            )))
+                        }
+                    })
+                    .await?;
+                }
+
+                Ok(())
+            },
+        )
+        .await;
+    }
+
+    success
+}
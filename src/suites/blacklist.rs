@@ -0,0 +1,180 @@
+//! Uploads by blacklisted users are rejected without leaving anything behind in the database,
+//! other users can still upload as normal.
+//!
+//! The blacklist only holds uploaders, the api has no way to blacklist a demo by its hash.
+
+use crate::fixtures::{GRANARY, PROCESS};
+use crate::harness::Harness;
+use crate::report::{assert_eq, Test};
+use crate::suites::row_counts;
+use crate::tags::Tag;
+use color_eyre::{Report, Result};
+
+const BLOCKED_STEAM_ID: u64 = 76561197992327511;
+const BLOCKED_TOKEN: &str = "blocked_token";
+
+fn expect_rejected(result: Result<u32, demostf_client::Error>) -> Result<()> {
+    match result {
+        Ok(id) => Err(Report::msg(format!(
+            "Expected upload to be rejected, got demo {}",
+            id
+        ))),
+        Err(demostf_client::Error::InvalidApiKey) => Ok(()),
+        Err(e) => Err(Report::msg(format!(
+            "Expected upload to be rejected as invalid key, got {}",
+            e
+        ))),
+    }
+}
+
+pub async fn run(harness: &Harness) -> bool {
+    let mut success = true;
+
+    success &= Test::run(
+        "Upload by blacklisted user",
+        &[Tag::Upload, Tag::Auth, Tag::Destructive],
+        harness,
+        |test| async move {
+            harness
+                .add_user(BLOCKED_STEAM_ID, "Blocked", BLOCKED_TOKEN)
+                .await?;
+            harness.blacklist_uploader(BLOCKED_STEAM_ID).await?;
+            let before = row_counts(harness).await?;
+
+            test.step("upload", |client| async move {
+                expect_rejected(
+                    client
+                        .upload_demo(
+                            String::from("test.dem"),
+                            GRANARY.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from(BLOCKED_TOKEN),
+                        )
+                        .await,
+                )
+            })
+            .await?;
+
+            test.step("private upload", |client| async move {
+                expect_rejected(
+                    client
+                        .upload_private_demo(
+                            String::from("test.dem"),
+                            GRANARY.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from(BLOCKED_TOKEN),
+                        )
+                        .await,
+                )
+            })
+            .await?;
+
+            test.step("no rows created", |_| async move {
+                assert_eq(row_counts(harness).await?, before)
+            })
+            .await?;
+
+            test.step("nothing listed", |client| async move {
+                let list = client
+                    .list_uploads(BLOCKED_STEAM_ID.into(), Default::default(), 1)
+                    .await?;
+                assert_eq(list.len(), 0)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Upload by other user while someone is blacklisted",
+        &[Tag::Upload, Tag::Auth, Tag::Destructive],
+        harness,
+        |test| async move {
+            harness
+                .add_user(BLOCKED_STEAM_ID, "Blocked", BLOCKED_TOKEN)
+                .await?;
+            harness.blacklist_uploader(BLOCKED_STEAM_ID).await?;
+
+            let id = test
+                .step("upload", |client| async move {
+                    Ok(client
+                        .upload_demo(
+                            String::from("test.dem"),
+                            PROCESS.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from("token"),
+                        )
+                        .await?)
+                })
+                .await?;
+
+            test.step("get demo", |client| async move {
+                let demo = client.get(id).await?;
+                assert_eq(demo.uploader.id(), 1)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Blacklisted user re-uploading existing demo",
+        &[Tag::Upload, Tag::Auth, Tag::Destructive],
+        harness,
+        |test| async move {
+            harness
+                .add_user(BLOCKED_STEAM_ID, "Blocked", BLOCKED_TOKEN)
+                .await?;
+            harness.blacklist_uploader(BLOCKED_STEAM_ID).await?;
+
+            let id = test
+                .step("upload", |client| async move {
+                    Ok(client
+                        .upload_demo(
+                            String::from("test.dem"),
+                            GRANARY.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from("token"),
+                        )
+                        .await?)
+                })
+                .await?;
+            let before = row_counts(harness).await?;
+
+            test.step("upload by blacklisted user", |client| async move {
+                expect_rejected(
+                    client
+                        .upload_demo(
+                            String::from("test.dem"),
+                            GRANARY.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from(BLOCKED_TOKEN),
+                        )
+                        .await,
+                )
+            })
+            .await?;
+
+            test.step("existing demo untouched", |client| async move {
+                let demo = client.get(id).await?;
+                assert_eq(demo.uploader.id(), 1)?;
+                assert_eq(row_counts(harness).await?, before)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
@@ -0,0 +1,203 @@
+//! Concurrent uploads
+//!
+//! Many uploads are sent at the same time, after which the database should be in the same state as
+//! if they were uploaded one by one: every hash stored once, no duplicate players, no gaps in the
+//! demo ids and every id returned to an uploader pointing to an existing demo.
+
+use crate::fixtures::{self, GRANARY};
+use crate::harness::Harness;
+use crate::report::{assert_eq, Test};
+use crate::tags::Tag;
+use color_eyre::{Report, Result};
+use demostf_client::ApiClient;
+
+const CONCURRENT_UPLOADS: u64 = 10;
+/// Steam ids for the extra uploaders, offset by the uploader index
+const UPLOADER_BASE: u64 = 76561198000000000;
+
+/// Upload all demos at the same time and return the ids in the same order
+async fn upload_concurrent(
+    client: &ApiClient,
+    uploads: Vec<(String, Vec<u8>, String)>,
+) -> Result<Vec<u32>> {
+    let handles: Vec<_> = uploads
+        .into_iter()
+        .map(|(name, data, token)| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                client
+                    .upload_demo(name, data, String::from("RED"), String::from("BLUE"), token)
+                    .await
+            })
+        })
+        .collect();
+
+    let mut ids = Vec::with_capacity(handles.len());
+    for handle in handles {
+        ids.push(handle.await??);
+    }
+    Ok(ids)
+}
+
+async fn verify_invariants(harness: &Harness, client: &ApiClient, ids: &[u32]) -> Result<()> {
+    assert_eq(harness.count_duplicates("demos", "hash").await?, 0)?;
+    assert_eq(
+        harness
+            .count_duplicates("players", "demo_id, user_id")
+            .await?,
+        0,
+    )?;
+
+    let (min, max) = harness.id_range("demos").await?;
+    let count = harness.count_rows("demos").await?;
+    if max - min + 1 != count {
+        return Err(Report::msg(format!(
+            "Demo ids {} to {} aren't contiguous for {} demos",
+            min, max, count
+        )));
+    }
+
+    for id in ids {
+        let demo = client.get(*id).await?;
+        assert_eq(demo.id, *id)?;
+    }
+    Ok(())
+}
+
+pub async fn run(harness: &Harness) -> bool {
+    let mut success = true;
+
+    success &= Test::run(
+        "Concurrent upload of the same demo",
+        &[Tag::Upload, Tag::Slow, Tag::Destructive],
+        harness,
+        |test| async move {
+            for index in 0..CONCURRENT_UPLOADS {
+                harness
+                    .add_user(
+                        UPLOADER_BASE + index,
+                        &format!("Uploader {}", index),
+                        &format!("token{}", index),
+                    )
+                    .await?;
+            }
+
+            let ids = test
+                .step("upload", |client| async move {
+                    let uploads = (0..CONCURRENT_UPLOADS)
+                        .map(|index| {
+                            (
+                                String::from("test.dem"),
+                                GRANARY.to_vec(),
+                                format!("token{}", index),
+                            )
+                        })
+                        .collect();
+                    upload_concurrent(client, uploads).await
+                })
+                .await?;
+            let ids = &ids;
+
+            test.step("same id returned", |_| async move {
+                for id in ids {
+                    assert_eq(*id, ids[0])?;
+                }
+                assert_eq(harness.count_rows("demos").await?, 1)
+            })
+            .await?;
+
+            test.step("invariants", |client| async move {
+                verify_invariants(harness, client, ids).await
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Concurrent upload of different demos",
+        &[Tag::Upload, Tag::Slow],
+        harness,
+        |test| async move {
+            let ids = test
+                .step("upload", |client| async move {
+                    let uploads = fixtures::ALL
+                        .iter()
+                        .map(|(name, data)| {
+                            (String::from(*name), data.to_vec(), String::from("token"))
+                        })
+                        .collect();
+                    upload_concurrent(client, uploads).await
+                })
+                .await?;
+            let ids = &ids;
+
+            test.step("invariants", |client| async move {
+                assert_eq(
+                    harness.count_rows("demos").await?,
+                    fixtures::ALL.len() as i64,
+                )?;
+                verify_invariants(harness, client, ids).await
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Concurrent upload with identical names",
+        &[Tag::Upload, Tag::Slow],
+        harness,
+        |test| async move {
+            let ids = test
+                .step("upload", |client| async move {
+                    let uploads = (0..CONCURRENT_UPLOADS)
+                        .map(|index| {
+                            (
+                                String::from("test.dem"),
+                                fixtures::unique(GRANARY, &format!("concurrent {}", index)),
+                                String::from("token"),
+                            )
+                        })
+                        .collect();
+                    upload_concurrent(client, uploads).await
+                })
+                .await?;
+            let ids = &ids;
+
+            test.step("invariants", |client| async move {
+                assert_eq(
+                    harness.count_rows("demos").await?,
+                    CONCURRENT_UPLOADS as i64,
+                )?;
+                verify_invariants(harness, client, ids).await
+            })
+            .await?;
+
+            test.step("distinct urls", |client| async move {
+                let mut urls = Vec::with_capacity(ids.len());
+                for id in ids {
+                    let url = client.get(*id).await?.url;
+                    if urls.contains(&url) {
+                        return Err(Report::msg(format!(
+                            "Demo {} stored at the same url as another demo: {}",
+                            id, url
+                        )));
+                    }
+                    urls.push(url);
+                }
+                Ok(())
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
@@ -0,0 +1,222 @@
+//! Exact status codes, headers and json shapes of the api endpoints, independent of what the typed
+//! client happens to accept. Response bodies are validated against the versioned schema in `schema/`.
+
+use crate::fixtures::GRANARY;
+use crate::harness::Harness;
+use crate::raw::RawResponse;
+use crate::report::Test;
+use crate::schema::Schema;
+use crate::tags::Tag;
+use color_eyre::{Report, Result};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+/// Common checks for every json endpoint
+fn expect_json(response: &RawResponse) -> Result<Value> {
+    response.expect_status(StatusCode::OK)?;
+    let content_type = response.header("content-type")?;
+    if !content_type.starts_with("application/json") {
+        return Err(Report::msg(format!(
+            "Expected json content type, got {}",
+            content_type
+        )));
+    }
+    expect_cors(response)?;
+    response.json()
+}
+
+fn expect_cors(response: &RawResponse) -> Result<()> {
+    let origin = response.header("access-control-allow-origin")?;
+    if origin != "*" {
+        return Err(Report::msg(format!(
+            "Expected cors to allow all origins, got {}",
+            origin
+        )));
+    }
+    Ok(())
+}
+
+fn expect_count(value: &Value, count: usize) -> Result<()> {
+    let len = value.as_array().map(Vec::len).unwrap_or_default();
+    if len != count {
+        return Err(Report::msg(format!(
+            "Expected {} items, got {}",
+            count, len
+        )));
+    }
+    Ok(())
+}
+
+fn expect_clean_error(response: &RawResponse, status: StatusCode) -> Result<()> {
+    response.expect_status(status)?;
+    response.expect_no_leak()
+}
+
+pub async fn run(harness: &Harness, edit_key: &str) -> bool {
+    let raw = &harness.raw();
+    let schema = match Schema::load() {
+        Ok(schema) => schema,
+        Err(e) => {
+            println!(" - Contract: failed to load schema: {:#}", e);
+            return false;
+        }
+    };
+    let schema = &schema;
+    let mut success = true;
+
+    success &= Test::run(
+        "Contract: read endpoints",
+        &[Tag::Upload, Tag::Listing],
+        harness,
+        |test| async move {
+            test.step("upload", |_| async move {
+                let response = raw
+                    .upload("test.dem", GRANARY, "RED", "BLUE", "token")
+                    .await?;
+                response.expect_status(StatusCode::OK)?;
+                schema.validate("uploadResult", &Value::String(response.body))
+            })
+            .await?;
+
+            test.step("demo", |_| async move {
+                let demo = expect_json(&raw.get("demos/1").await?)?;
+                schema.validate("demo", &demo)?;
+                expect_count(&demo["players"], 12)
+            })
+            .await?;
+
+            test.step("list", |_| async move {
+                let list = expect_json(&raw.get("demos").await?)?;
+                schema.validate("demoList", &list)?;
+                expect_count(&list, 1)
+            })
+            .await?;
+
+            test.step("list page 2", |_| async move {
+                let list = expect_json(&raw.get("demos?page=2").await?)?;
+                schema.validate("demoList", &list)?;
+                expect_count(&list, 0)
+            })
+            .await?;
+
+            test.step("uploads", |_| async move {
+                let list = expect_json(&raw.get("uploads/76561198024494988").await?)?;
+                schema.validate("demoList", &list)?;
+                expect_count(&list, 1)
+            })
+            .await?;
+
+            test.step("chat", |_| async move {
+                let chat = expect_json(&raw.get("demos/1/chat").await?)?;
+                schema.validate("chat", &chat)
+            })
+            .await?;
+
+            test.step("user", |_| async move {
+                let user = expect_json(&raw.get("users/1").await?)?;
+                schema.validate("user", &user)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Contract: error responses",
+        &[Tag::Auth],
+        harness,
+        |test| async move {
+            test.step("unknown demo", |_| async move {
+                expect_clean_error(&raw.get("demos/999").await?, StatusCode::NOT_FOUND)
+            })
+            .await?;
+
+            test.step("chat of unknown demo", |_| async move {
+                expect_clean_error(&raw.get("demos/999/chat").await?, StatusCode::NOT_FOUND)
+            })
+            .await?;
+
+            test.step("unknown user", |_| async move {
+                expect_clean_error(&raw.get("users/999").await?, StatusCode::NOT_FOUND)
+            })
+            .await?;
+
+            test.step("upload with invalid key", |_| async move {
+                let response = raw
+                    .upload("test.dem", GRANARY, "RED", "BLUE", "wrong_token")
+                    .await?;
+                expect_clean_error(&response, StatusCode::UNAUTHORIZED)?;
+                if response.body != "Invalid key" {
+                    return Err(Report::msg(format!(
+                        "Unexpected error body {:?}",
+                        response.body
+                    )));
+                }
+                Ok(())
+            })
+            .await?;
+
+            test.step("set url with invalid key", |_| async move {
+                raw.upload("test.dem", GRANARY, "RED", "BLUE", "token")
+                    .await?
+                    .expect_status(StatusCode::OK)?;
+                let hash = format!("{:x}", md5::compute(GRANARY));
+                let response = raw
+                    .post_form(
+                        "demos/1/url",
+                        &[
+                            ("hash", hash.as_str()),
+                            ("backend", "example"),
+                            ("url", "https://example.com/somedemo.dem"),
+                            ("path", "somedemo.dem"),
+                            ("key", "invalid"),
+                        ],
+                    )
+                    .await?;
+                expect_clean_error(&response, StatusCode::UNAUTHORIZED)
+            })
+            .await?;
+
+            test.step("set url with invalid hash", |_| async move {
+                let response = raw
+                    .post_form(
+                        "demos/1/url",
+                        &[
+                            ("hash", "01010101010101010101010101010101"),
+                            ("backend", "example"),
+                            ("url", "https://example.com/somedemo.dem"),
+                            ("path", "somedemo.dem"),
+                            ("key", edit_key),
+                        ],
+                    )
+                    .await?;
+                expect_clean_error(&response, StatusCode::PRECONDITION_FAILED)
+            })
+            .await?;
+
+            test.step("set url of unknown demo", |_| async move {
+                let response = raw
+                    .post_form(
+                        "demos/999/url",
+                        &[
+                            ("hash", "01010101010101010101010101010101"),
+                            ("backend", "example"),
+                            ("url", "https://example.com/somedemo.dem"),
+                            ("path", "somedemo.dem"),
+                            ("key", edit_key),
+                        ],
+                    )
+                    .await?;
+                expect_clean_error(&response, StatusCode::NOT_FOUND)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
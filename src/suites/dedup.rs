@@ -0,0 +1,244 @@
+//! Re-uploading a demo that already exists (by hash) doesn't create a new demo, instead the id of the
+//! existing demo is returned and the existing demo is left untouched, regardless of who re-uploads it,
+//! under what name, with which team names or with what visibility.
+
+use crate::fixtures::GRANARY;
+use crate::harness::Harness;
+use crate::report::{assert_eq, Test};
+use crate::suites::{row_counts, upload_fixture, upload_private_fixture, upload_with_teams};
+use crate::tags::Tag;
+
+const OTHER_STEAM_ID: u64 = 76561197992327511;
+const OTHER_TOKEN: &str = "other_token";
+
+pub async fn run(harness: &Harness, edit_key: &str) -> bool {
+    let mut success = true;
+
+    success &= Test::run(
+        "Re-upload by other user",
+        &[Tag::Upload, Tag::Destructive],
+        harness,
+        |test| async move {
+            harness
+                .add_user(OTHER_STEAM_ID, "Other", OTHER_TOKEN)
+                .await?;
+
+            let id = test
+                .step("upload", |client| async move {
+                    upload_fixture(client, "test.dem", GRANARY).await
+                })
+                .await?;
+            let before = row_counts(harness).await?;
+
+            test.step("upload by other user", |client| async move {
+                let new_id = client
+                    .upload_demo(
+                        String::from("test.dem"),
+                        GRANARY.to_vec(),
+                        String::from("RED"),
+                        String::from("BLUE"),
+                        String::from(OTHER_TOKEN),
+                    )
+                    .await?;
+                assert_eq(new_id, id)
+            })
+            .await?;
+
+            test.step("uploader unchanged", |client| async move {
+                let demo = client.get(id).await?;
+                assert_eq(demo.uploader.id(), 1)?;
+
+                let list = client
+                    .list_uploads(OTHER_STEAM_ID.into(), Default::default(), 1)
+                    .await?;
+                assert_eq(list.len(), 0)
+            })
+            .await?;
+
+            test.step("no duplicate rows", |_| async move {
+                assert_eq(row_counts(harness).await?, before)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Re-upload with different name and team names",
+        &[Tag::Upload],
+        harness,
+        |test| async move {
+            let id = test
+                .step("upload", |client| async move {
+                    upload_fixture(client, "test.dem", GRANARY).await
+                })
+                .await?;
+            let before = row_counts(harness).await?;
+
+            test.step("upload with other name", |client| async move {
+                let new_id = upload_fixture(client, "renamed.dem", GRANARY).await?;
+                assert_eq(new_id, id)
+            })
+            .await?;
+
+            test.step("upload with other team names", |client| async move {
+                let new_id = upload_with_teams(client, "test.dem", GRANARY, "Foo", "Bar").await?;
+                assert_eq(new_id, id)
+            })
+            .await?;
+
+            test.step("original kept", |client| async move {
+                let demo = client.get(id).await?;
+                assert_object_eq!(demo => {
+                    name == "test.dem",
+                    red == "RED",
+                    blue == "BLUE",
+                });
+                Ok(())
+            })
+            .await?;
+
+            test.step("no duplicate rows", |_| async move {
+                assert_eq(row_counts(harness).await?, before)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Public re-upload of private demo",
+        &[Tag::Upload, Tag::Private],
+        harness,
+        |test| async move {
+            let id = test
+                .step("upload private", |client| async move {
+                    upload_private_fixture(client, "test.dem", GRANARY).await
+                })
+                .await?;
+            let before = row_counts(harness).await?;
+
+            test.step("upload public", |client| async move {
+                let new_id = upload_fixture(client, "test.dem", GRANARY).await?;
+                assert_eq(new_id, id)
+            })
+            .await?;
+
+            test.step("still private", |client| async move {
+                let demo = client.get(id).await?;
+                assert_object_eq!(demo => {
+                    url == "",
+                    backend == "",
+                });
+                Ok(())
+            })
+            .await?;
+
+            test.step("no duplicate rows", |_| async move {
+                assert_eq(row_counts(harness).await?, before)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Private re-upload of public demo",
+        &[Tag::Upload, Tag::Private],
+        harness,
+        |test| async move {
+            let id = test
+                .step("upload public", |client| async move {
+                    upload_fixture(client, "test.dem", GRANARY).await
+                })
+                .await?;
+            let before = row_counts(harness).await?;
+
+            test.step("upload private", |client| async move {
+                let new_id = upload_private_fixture(client, "test.dem", GRANARY).await?;
+                assert_eq(new_id, id)
+            })
+            .await?;
+
+            test.step("still public", |client| async move {
+                let demo = client.get(id).await?;
+                assert_object_eq!(demo => {
+                    url == "https://localhost/ec/68/ec681d1b4846a7e3cb2c129fcbd858ac_test.dem",
+                    backend == "static",
+                });
+                Ok(())
+            })
+            .await?;
+
+            test.step("no duplicate rows", |_| async move {
+                assert_eq(row_counts(harness).await?, before)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Re-upload after set url",
+        &[Tag::Upload, Tag::Auth],
+        harness,
+        |test| async move {
+            let id = test
+                .step("upload", |client| async move {
+                    upload_fixture(client, "test.dem", GRANARY).await
+                })
+                .await?;
+
+            test.step("set url", |client| async move {
+                let demo = client.get(id).await?;
+                client
+                    .set_url(
+                        id,
+                        "example",
+                        "somedemo.dem",
+                        "https://example.com/somedemo.dem",
+                        demo.hash,
+                        edit_key,
+                    )
+                    .await?;
+                Ok(())
+            })
+            .await?;
+            let before = row_counts(harness).await?;
+
+            test.step("upload again", |client| async move {
+                let new_id = upload_fixture(client, "test.dem", GRANARY).await?;
+                assert_eq(new_id, id)
+            })
+            .await?;
+
+            test.step("url kept", |client| async move {
+                let demo = client.get(id).await?;
+                assert_object_eq!(demo => {
+                    url == "https://example.com/somedemo.dem",
+                    backend == "example",
+                });
+                Ok(())
+            })
+            .await?;
+
+            test.step("no duplicate rows", |_| async move {
+                assert_eq(row_counts(harness).await?, before)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
@@ -0,0 +1,188 @@
+//! Downloading a stored demo over http, including the partial and conditional requests demo
+//! viewers use when streaming a demo.
+
+use crate::fixtures::GRANARY;
+use crate::harness::Harness;
+use crate::report::{assert_eq, Test};
+use crate::storage::storage_path;
+use crate::tags::Tag;
+use color_eyre::{Report, Result};
+use colored::Colorize;
+use reqwest::header::{
+    HeaderName, ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+    ETAG, IF_NONE_MATCH, RANGE,
+};
+use reqwest::{Client, Response, StatusCode};
+
+fn header(response: &Response, name: HeaderName) -> Result<String> {
+    let value = response
+        .headers()
+        .get(&name)
+        .ok_or_else(|| Report::msg(format!("Missing {} header", name)))?;
+    Ok(value.to_str()?.to_string())
+}
+
+async fn get_range(client: &Client, url: &str, range: &str) -> Result<Response> {
+    Ok(client.get(url).header(RANGE, range).send().await?)
+}
+
+async fn expect_partial(response: Response, start: usize, end: usize, data: &[u8]) -> Result<()> {
+    assert_eq(response.status(), StatusCode::PARTIAL_CONTENT)?;
+    assert_eq(
+        header(&response, CONTENT_RANGE)?,
+        format!("bytes {}-{}/{}", start, end - 1, data.len()),
+    )?;
+    assert_eq(
+        header(&response, CONTENT_LENGTH)?,
+        (end - start).to_string(),
+    )?;
+    let body = response.bytes().await?;
+    if body[..] != data[start..end] {
+        return Err(Report::msg(
+            "Partial content doesn't match the uploaded data",
+        ));
+    }
+    Ok(())
+}
+
+pub async fn run(harness: &Harness) -> bool {
+    let static_url = match dotenv::var("STATIC_URL") {
+        Ok(url) => url.trim_end_matches('/').to_string(),
+        Err(_) => {
+            println!(
+                " - Download demo: {}",
+                "skipped, STATIC_URL not set".yellow()
+            );
+            return true;
+        }
+    };
+    let static_url = &static_url;
+    let http = &Client::new();
+    let mut success = true;
+
+    success &= Test::run(
+        "Download demo",
+        &[Tag::Upload],
+        harness,
+        |test| async move {
+            let url = test
+                .step("upload", |client| async move {
+                    let id = client
+                        .upload_demo(
+                            String::from("test.dem"),
+                            GRANARY.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from("token"),
+                        )
+                        .await?;
+                    let demo = client.get(id).await?;
+                    Ok(format!(
+                        "{}/{}",
+                        static_url,
+                        storage_path(&demo.hash, "test.dem")
+                    ))
+                })
+                .await?;
+            let url = url.as_str();
+
+            let etag = test
+                .step("full download", |_| async move {
+                    let response = http.get(url).send().await?;
+                    assert_eq(response.status(), StatusCode::OK)?;
+                    assert_eq(
+                        header(&response, CONTENT_LENGTH)?,
+                        GRANARY.len().to_string(),
+                    )?;
+                    assert_eq(header(&response, CONTENT_TYPE)?, "application/octet-stream")?;
+                    let disposition = header(&response, CONTENT_DISPOSITION)?;
+                    if !disposition.starts_with("attachment") {
+                        return Err(Report::msg(format!(
+                            "Expected attachment disposition, got {}",
+                            disposition
+                        )));
+                    }
+                    assert_eq(header(&response, ACCEPT_RANGES)?, "bytes")?;
+                    let etag = header(&response, ETAG)?;
+
+                    let body = response.bytes().await?;
+                    if body[..] != GRANARY[..] {
+                        return Err(Report::msg(
+                            "Downloaded demo doesn't match the uploaded data",
+                        ));
+                    }
+                    Ok(etag)
+                })
+                .await?;
+            let etag = etag.as_str();
+
+            test.step("range request", |_| async move {
+                let response = get_range(http, url, "bytes=0-1023").await?;
+                expect_partial(response, 0, 1024, GRANARY).await
+            })
+            .await?;
+
+            test.step("open ended range request", |_| async move {
+                let response = get_range(http, url, "bytes=1000-").await?;
+                expect_partial(response, 1000, GRANARY.len(), GRANARY).await
+            })
+            .await?;
+
+            test.step("suffix range request", |_| async move {
+                let response = get_range(http, url, "bytes=-100").await?;
+                expect_partial(response, GRANARY.len() - 100, GRANARY.len(), GRANARY).await
+            })
+            .await?;
+
+            test.step("unsatisfiable range request", |_| async move {
+                let response = get_range(http, url, &format!("bytes={}-", GRANARY.len())).await?;
+                assert_eq(response.status(), StatusCode::RANGE_NOT_SATISFIABLE)?;
+                assert_eq(
+                    header(&response, CONTENT_RANGE)?,
+                    format!("bytes */{}", GRANARY.len()),
+                )
+            })
+            .await?;
+
+            test.step("if-none-match with etag", |_| async move {
+                let response = http.get(url).header(IF_NONE_MATCH, etag).send().await?;
+                assert_eq(response.status(), StatusCode::NOT_MODIFIED)?;
+                assert_eq(response.bytes().await?.len(), 0)
+            })
+            .await?;
+
+            test.step("if-none-match with other etag", |_| async move {
+                let response = http
+                    .get(url)
+                    .header(IF_NONE_MATCH, "\"not-the-etag\"")
+                    .send()
+                    .await?;
+                assert_eq(response.status(), StatusCode::OK)?;
+                assert_eq(response.bytes().await?.len(), GRANARY.len())
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Download missing demo",
+        &[Tag::Upload],
+        harness,
+        |test| async move {
+            test.step("download", |_| async move {
+                let url = format!("{}/{}", static_url, storage_path(&[0; 16], "test.dem"));
+                let response = http.get(&url).send().await?;
+                assert_eq(response.status(), StatusCode::NOT_FOUND)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
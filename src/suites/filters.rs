@@ -0,0 +1,188 @@
+//! Game type and map filters
+//!
+//! Game type filters select demos by their player count, demos with a player count that doesn't
+//! belong to any format (like ultiduo) are only found without a type filter. Map filters are case
+//! sensitive prefix matches, wildcard characters in the filter are matched literally.
+
+use crate::fixtures;
+use crate::format::Format;
+use crate::harness::Harness;
+use crate::report::{assert_eq, Test};
+use crate::suites::upload_fixture;
+use crate::tags::Tag;
+use crate::verify::parse_demo;
+use color_eyre::Result;
+use demostf_client::{ApiClient, ListOrder, ListParams};
+
+/// Player counts that don't match any format, set on copies of the first fixture
+const ODD_PLAYER_COUNTS: [u8; 3] = [2, 5, 24];
+
+async fn list_ids(client: &ApiClient, params: ListParams) -> Result<Vec<u32>> {
+    Ok(client
+        .list(params.with_order(ListOrder::Ascending), 1)
+        .await?
+        .iter()
+        .map(|demo| demo.id)
+        .collect())
+}
+
+/// Upload all fixtures, returning the id, map and player count for each
+async fn upload_fixtures(client: &ApiClient) -> Result<Vec<(u32, String, usize)>> {
+    let mut demos = Vec::with_capacity(fixtures::ALL.len());
+    for (name, data) in fixtures::ALL {
+        let (header, state) = parse_demo(data)?;
+        let player_count = state
+            .users
+            .values()
+            .filter(|user| user.team.is_player())
+            .count();
+        let id = upload_fixture(client, name, data).await?;
+        demos.push((id, header.map, player_count));
+    }
+    Ok(demos)
+}
+
+pub async fn run(harness: &Harness) -> bool {
+    let mut success = true;
+
+    success &= Test::run(
+        "Game type filters",
+        &[Tag::Listing, Tag::Destructive],
+        harness,
+        |test| async move {
+            let mut demos = test
+                .step(
+                    "upload",
+                    |client| async move { upload_fixtures(client).await },
+                )
+                .await?;
+
+            let mut player_counts: Vec<u8> = Format::ALL
+                .iter()
+                .map(|format| format.player_count() as u8)
+                .collect();
+            player_counts.extend_from_slice(&ODD_PLAYER_COUNTS);
+            harness.clone_demo(1, player_counts.len() as u32).await?;
+            for (i, count) in player_counts.iter().enumerate() {
+                let id = (fixtures::ALL.len() + i + 1) as u32;
+                harness.set_player_count(id, *count).await?;
+                demos.push((id, String::new(), *count as usize));
+            }
+            let demos = &demos;
+
+            for format in Format::ALL {
+                test.step(&format!("{:?}", format), |client| async move {
+                    let expected: Vec<u32> = demos
+                        .iter()
+                        .filter(|(_, _, count)| *count == format.player_count())
+                        .map(|(id, _, _)| *id)
+                        .collect();
+                    let list =
+                        list_ids(client, ListParams::default().with_type(format.game_type()))
+                            .await?;
+                    assert_eq(list, expected)
+                })
+                .await?;
+            }
+
+            test.step("player counts without format", |client| async move {
+                let unmatched: Vec<u32> = demos
+                    .iter()
+                    .filter(|(_, _, count)| {
+                        !Format::ALL
+                            .iter()
+                            .any(|format| format.player_count() == *count)
+                    })
+                    .map(|(id, _, _)| *id)
+                    .collect();
+
+                let mut typed = Vec::new();
+                for format in Format::ALL {
+                    typed.extend(
+                        list_ids(client, ListParams::default().with_type(format.game_type()))
+                            .await?,
+                    );
+                }
+                let all = list_ids(client, ListParams::default()).await?;
+
+                for id in unmatched {
+                    assert_eq(typed.contains(&id), false)?;
+                    assert_eq(all.contains(&id), true)?;
+                }
+                Ok(())
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run("Map filters", &[Tag::Listing], harness, |test| async move {
+        let demos = test
+            .step(
+                "upload",
+                |client| async move { upload_fixtures(client).await },
+            )
+            .await?;
+        let demos = &demos;
+
+        test.step("exact map names", |client| async move {
+            for (_, map, _) in demos {
+                let expected: Vec<u32> = demos
+                    .iter()
+                    .filter(|(_, other, _)| other.starts_with(map.as_str()))
+                    .map(|(id, _, _)| *id)
+                    .collect();
+                let list = list_ids(client, ListParams::default().with_map(map.as_str())).await?;
+                assert_eq(list, expected)?;
+            }
+            Ok(())
+        })
+        .await?;
+
+        test.step("map prefixes", |client| async move {
+            for prefix in ["cp_", "cp_gr", "koth_", "ultiduo_"] {
+                let expected: Vec<u32> = demos
+                    .iter()
+                    .filter(|(_, map, _)| map.starts_with(prefix))
+                    .map(|(id, _, _)| *id)
+                    .collect();
+                let list = list_ids(client, ListParams::default().with_map(prefix)).await?;
+                assert_eq(list, expected)?;
+            }
+            Ok(())
+        })
+        .await?;
+
+        test.step("case differences", |client| async move {
+            for (_, map, _) in demos {
+                let upper = map.to_uppercase();
+                let list = list_ids(client, ListParams::default().with_map(upper.as_str())).await?;
+                assert_eq(list.len(), 0)?;
+            }
+            Ok(())
+        })
+        .await?;
+
+        test.step("wildcards", |client| async move {
+            for pattern in ["%", "cp%", "cp_%", "*", "cp_*", "cp?granary", "_p_granary"] {
+                let list = list_ids(client, ListParams::default().with_map(pattern)).await?;
+                assert_eq(list.len(), 0)?;
+            }
+            Ok(())
+        })
+        .await?;
+
+        test.step("unknown map", |client| async move {
+            let list = list_ids(client, ListParams::default().with_map("pl_unknown")).await?;
+            assert_eq(list.len(), 0)
+        })
+        .await?;
+
+        Ok(())
+    })
+    .await;
+
+    success
+}
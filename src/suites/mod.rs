@@ -0,0 +1,76 @@
+//! Test families that go beyond the basic upload and listing tests in `main.rs`
+
+use crate::harness::Harness;
+use color_eyre::Result;
+use demostf_client::ApiClient;
+
+pub mod auth;
+pub mod blacklist;
+pub mod concurrency;
+pub mod contract;
+pub mod dedup;
+pub mod download;
+pub mod filters;
+pub mod pagination;
+pub mod privacy;
+pub mod security;
+pub mod storage;
+pub mod storage_keys;
+pub mod teams;
+pub mod timing;
+pub mod users;
+
+/// Number of rows in the tables filled by an upload
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RowCounts {
+    demos: i64,
+    players: i64,
+    chat: i64,
+    teams: i64,
+}
+
+pub async fn row_counts(harness: &Harness) -> Result<RowCounts> {
+    Ok(RowCounts {
+        demos: harness.count_rows("demos").await?,
+        players: harness.count_rows("players").await?,
+        chat: harness.count_rows("chat").await?,
+        teams: harness.count_rows("teams").await?,
+    })
+}
+
+/// Upload a demo as the default uploader, with the default team names
+pub async fn upload_fixture(client: &ApiClient, name: &str, data: &[u8]) -> Result<u32> {
+    upload_with_teams(client, name, data, "RED", "BLUE").await
+}
+
+/// Upload a demo as the default uploader
+pub async fn upload_with_teams(
+    client: &ApiClient,
+    name: &str,
+    data: &[u8],
+    red: &str,
+    blue: &str,
+) -> Result<u32> {
+    Ok(client
+        .upload_demo(
+            String::from(name),
+            data.to_vec(),
+            String::from(red),
+            String::from(blue),
+            String::from("token"),
+        )
+        .await?)
+}
+
+/// Upload a private demo as the default uploader, with the default team names
+pub async fn upload_private_fixture(client: &ApiClient, name: &str, data: &[u8]) -> Result<u32> {
+    Ok(client
+        .upload_private_demo(
+            String::from(name),
+            data.to_vec(),
+            String::from("RED"),
+            String::from("BLUE"),
+            String::from("token"),
+        )
+        .await?)
+}
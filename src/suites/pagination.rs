@@ -0,0 +1,230 @@
+//! Paging through a large number of demos returns every demo exactly once, in order, with a fixed
+//! page size.
+
+use crate::fixtures;
+use crate::harness::Harness;
+use crate::raw::RawClient;
+use crate::report::{assert_eq, Test};
+use crate::tags::Tag;
+use color_eyre::{Report, Result};
+use demostf_client::{ApiClient, ListOrder, ListParams, SteamID};
+use serde_json::Value;
+
+/// Copies inserted for each uploaded fixture
+const CLONES: u32 = 50;
+const UPLOADER: u64 = 76561198024494988;
+/// Stop paging if the api keeps returning results
+const MAX_PAGES: u32 = 100;
+
+fn params(ascending: bool) -> ListParams {
+    let order = if ascending {
+        ListOrder::Ascending
+    } else {
+        ListOrder::Descending
+    };
+    ListParams::default().with_order(order)
+}
+
+async fn list_page(
+    client: &ApiClient,
+    uploader: Option<u64>,
+    ascending: bool,
+    page: u32,
+) -> Result<Vec<u32>> {
+    let list = match uploader {
+        Some(uploader) => {
+            client
+                .list_uploads(SteamID::from(uploader), params(ascending), page)
+                .await?
+        }
+        None => client.list(params(ascending), page).await?,
+    };
+    Ok(list.iter().map(|demo| demo.id).collect())
+}
+
+async fn all_pages(
+    client: &ApiClient,
+    uploader: Option<u64>,
+    ascending: bool,
+) -> Result<Vec<Vec<u32>>> {
+    let mut pages = Vec::new();
+    for page in 1..=MAX_PAGES {
+        let ids = list_page(client, uploader, ascending, page).await?;
+        if ids.is_empty() {
+            return Ok(pages);
+        }
+        pages.push(ids);
+    }
+    Err(Report::msg(format!(
+        "Still getting results after {} pages",
+        MAX_PAGES
+    )))
+}
+
+/// Check that all pages but the last are full and that together they contain exactly the expected ids in order
+fn verify_pages(pages: &[Vec<u32>], expected: &[u32]) -> Result<()> {
+    let page_size = pages.first().map(Vec::len).unwrap_or_default();
+    if pages.len() < 2 {
+        return Err(Report::msg(format!(
+            "Expected multiple pages, got {} pages of {}",
+            pages.len(),
+            page_size
+        )));
+    }
+
+    let (last, full) = pages.split_last().unwrap();
+    for (i, page) in full.iter().enumerate() {
+        if page.len() != page_size {
+            return Err(Report::msg(format!(
+                "Page {} has {} demos, expected page size {}",
+                i + 1,
+                page.len(),
+                page_size
+            )));
+        }
+    }
+    if last.len() > page_size {
+        return Err(Report::msg(format!(
+            "Last page has {} demos, more than the page size {}",
+            last.len(),
+            page_size
+        )));
+    }
+
+    let ids: Vec<u32> = pages.iter().flatten().copied().collect();
+    for (i, id) in ids.iter().enumerate() {
+        if ids[..i].contains(id) {
+            return Err(Report::msg(format!(
+                "Demo {} is listed on multiple pages",
+                id
+            )));
+        }
+    }
+    if let Some(missing) = expected.iter().find(|id| !ids.contains(id)) {
+        return Err(Report::msg(format!(
+            "Demo {} is missing from the pages",
+            missing
+        )));
+    }
+    assert_eq(ids, expected.to_vec())
+}
+
+/// Out of range pages should either be rejected or handled gracefully, never cause a server error
+async fn verify_edge_page(raw: &RawClient, page: &str, first_page: &[u32]) -> Result<()> {
+    let response = raw.get(&format!("demos?page={}", page)).await?;
+    if response.status.is_client_error() {
+        return Ok(());
+    }
+    if !response.status.is_success() {
+        return Err(Report::msg(format!(
+            "Unexpected status {} for page {}",
+            response.status, page
+        )));
+    }
+    let ids: Vec<u64> = response
+        .json()?
+        .as_array()
+        .ok_or_else(|| Report::msg("Expected a list of demos"))?
+        .iter()
+        .filter_map(|demo| demo.get("id").and_then(Value::as_u64))
+        .collect();
+    let first_page: Vec<u64> = first_page.iter().map(|id| *id as u64).collect();
+    if !ids.is_empty() && ids != first_page {
+        return Err(Report::msg(format!(
+            "Page {} returned demos {:?}, expected either nothing or the first page",
+            page, ids
+        )));
+    }
+    Ok(())
+}
+
+pub async fn run(harness: &Harness) -> bool {
+    let raw = &harness.raw();
+
+    Test::run(
+        "Pagination",
+        &[Tag::Listing, Tag::Slow, Tag::Destructive],
+        harness,
+        |test| async move {
+            // the clones are inserted outside of the upload steps, so retrying an upload doesn't
+            // clone the demo twice
+            for (name, data) in fixtures::ALL {
+                let id = test
+                    .step(&format!("upload {}", name), |client| async move {
+                        Ok(client
+                            .upload_demo(
+                                String::from(*name),
+                                data.to_vec(),
+                                String::from("RED"),
+                                String::from("BLUE"),
+                                String::from("token"),
+                            )
+                            .await?)
+                    })
+                    .await?;
+                harness.clone_demo(id, CLONES).await?;
+            }
+
+            let total = harness.count_rows("demos").await? as u32;
+            let ascending: Vec<u32> = (1..=total).collect();
+            let descending: Vec<u32> = ascending.iter().rev().copied().collect();
+            let (ascending, descending) = (&ascending, &descending);
+
+            test.step("list descending", |client| async move {
+                verify_pages(&all_pages(client, None, false).await?, descending)
+            })
+            .await?;
+
+            test.step("list ascending", |client| async move {
+                verify_pages(&all_pages(client, None, true).await?, ascending)
+            })
+            .await?;
+
+            test.step("list uploads descending", |client| async move {
+                verify_pages(&all_pages(client, Some(UPLOADER), false).await?, descending)
+            })
+            .await?;
+
+            test.step("list uploads ascending", |client| async move {
+                verify_pages(&all_pages(client, Some(UPLOADER), true).await?, ascending)
+            })
+            .await?;
+
+            test.step("stable ordering", |client| async move {
+                for page in 1..=3 {
+                    let first = list_page(client, None, false, page).await?;
+                    let second = list_page(client, None, false, page).await?;
+                    assert_eq(second, first)?;
+                }
+                Ok(())
+            })
+            .await?;
+
+            let first_page = test
+                .step("first page", |client| async move {
+                    list_page(client, None, false, 1).await
+                })
+                .await?;
+            let first_page = &first_page;
+
+            test.step("page 0", |_| async move {
+                verify_edge_page(raw, "0", first_page).await
+            })
+            .await?;
+
+            test.step("negative page", |_| async move {
+                verify_edge_page(raw, "-1", first_page).await
+            })
+            .await?;
+
+            test.step("very large page", |client| async move {
+                assert_eq(list_page(client, None, false, u32::MAX).await?.len(), 0)?;
+                verify_edge_page(raw, "99999999999999999999", &[]).await
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await
+}
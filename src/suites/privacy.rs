@@ -0,0 +1,197 @@
+//! Visibility of private demos
+//!
+//! Private demos are listed and can be fetched like any other demo, but the `url` and `backend`
+//! are only shown when using the access key or the token of the uploader. Once the demo becomes
+//! public the url is shown to everyone.
+
+use crate::fixtures::GRANARY;
+use crate::harness::Harness;
+use crate::report::{assert_eq, Test};
+use crate::tags::Tag;
+use crate::verify::{parse_demo, verify_chat};
+use color_eyre::{Report, Result};
+use demostf_client::{ApiClient, ListParams, SteamID};
+use time::{Duration, OffsetDateTime};
+
+const UPLOADER: u64 = 76561198024494988;
+const PLAYER: u64 = 76561197992327511;
+const URL: &str = "https://localhost/ec/68/ec681d1b4846a7e3cb2c129fcbd858ac_test.dem";
+
+#[derive(Debug, Clone, Copy)]
+enum Viewer {
+    Anonymous,
+    WrongKey,
+    AccessKey,
+    Uploader,
+}
+
+impl Viewer {
+    const ALL: [Viewer; 4] = [
+        Viewer::Anonymous,
+        Viewer::WrongKey,
+        Viewer::AccessKey,
+        Viewer::Uploader,
+    ];
+
+    fn client(self, client: &ApiClient, access_key: &str) -> ApiClient {
+        let mut client = client.clone();
+        match self {
+            Viewer::Anonymous => {}
+            Viewer::WrongKey => client.set_access_key("wrong".into()),
+            Viewer::AccessKey => client.set_access_key(access_key.into()),
+            Viewer::Uploader => client.set_access_key("token".into()),
+        }
+        client
+    }
+
+    fn can_see_private(self) -> bool {
+        matches!(self, Viewer::AccessKey | Viewer::Uploader)
+    }
+}
+
+/// Check the url and backend of every place the demo shows up for the viewer
+async fn verify_visibility(client: &ApiClient, id: u32, visible: bool) -> Result<()> {
+    let (url, backend) = if visible { (URL, "static") } else { ("", "") };
+
+    let demo = client.get(id).await?;
+    assert_object_eq!(demo => {
+        url == url,
+        backend == backend,
+    });
+
+    let lists = [
+        ("list", client.list(ListParams::default(), 1).await?),
+        (
+            "uploads",
+            client
+                .list_uploads(SteamID::from(UPLOADER), ListParams::default(), 1)
+                .await?,
+        ),
+        (
+            "player list",
+            client
+                .list(ListParams::default().with_players(vec![PLAYER]), 1)
+                .await?,
+        ),
+    ];
+
+    for (name, list) in lists.iter() {
+        let listed = list
+            .iter()
+            .find(|demo| demo.id == id)
+            .ok_or_else(|| Report::msg(format!("Demo {} not found in {}", id, name)))?;
+        assert_object_eq!(listed => {
+            url == url,
+            backend == backend,
+        });
+    }
+
+    Ok(())
+}
+
+pub async fn run(harness: &Harness, access_key: &str) -> bool {
+    let mut success = true;
+
+    success &= Test::run(
+        "Private demo visibility",
+        &[Tag::Private, Tag::Listing, Tag::Destructive],
+        harness,
+        |test| async move {
+            let id = test
+                .step("upload", |client| async move {
+                    Ok(client
+                        .upload_private_demo(
+                            String::from("test.dem"),
+                            GRANARY.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from("token"),
+                        )
+                        .await?)
+                })
+                .await?;
+
+            for viewer in Viewer::ALL.iter().copied() {
+                test.step(
+                    &format!("visibility for {:?}", viewer),
+                    |client| async move {
+                        let client = viewer.client(client, access_key);
+                        verify_visibility(&client, id, viewer.can_see_private()).await
+                    },
+                )
+                .await?;
+            }
+
+            test.step("chat", |client| async move {
+                let (_, state) = parse_demo(GRANARY)?;
+                for viewer in Viewer::ALL.iter().copied() {
+                    let chat = viewer.client(client, access_key).get_chat(id).await?;
+                    verify_chat(&chat, &state)?;
+                }
+                Ok(())
+            })
+            .await?;
+
+            harness
+                .set_private_until(id, OffsetDateTime::now_utc() - Duration::minutes(1))
+                .await?;
+
+            for viewer in Viewer::ALL.iter().copied() {
+                test.step(
+                    &format!("visibility for {:?} after becoming public", viewer),
+                    |client| async move {
+                        let client = viewer.client(client, access_key);
+                        verify_visibility(&client, id, true).await
+                    },
+                )
+                .await?;
+            }
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Private demo not shown to other uploaders",
+        &[Tag::Private, Tag::Destructive],
+        harness,
+        |test| async move {
+            harness.add_user(PLAYER, "Other", "other_token").await?;
+
+            let id = test
+                .step("upload", |client| async move {
+                    Ok(client
+                        .upload_private_demo(
+                            String::from("test.dem"),
+                            GRANARY.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from("token"),
+                        )
+                        .await?)
+                })
+                .await?;
+
+            test.step("get with other token", |client| async move {
+                let mut client = client.clone();
+                client.set_access_key("other_token".into());
+                verify_visibility(&client, id, false).await
+            })
+            .await?;
+
+            test.step("not in other uploads", |client| async move {
+                let list = client
+                    .list_uploads(SteamID::from(PLAYER), ListParams::default(), 1)
+                    .await?;
+                assert_eq(list.len(), 0)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
@@ -0,0 +1,333 @@
+//! Injection payloads, path traversal strings, nul bytes, huge strings and unicode edge cases in
+//! every user controlled input. None of them should cause a server error or leak backend details,
+//! values that are accepted should be stored as is and nothing else in the database should change.
+
+use crate::fixtures::{self, GRANARY};
+use crate::harness::Harness;
+use crate::raw::{RawClient, RawResponse};
+use crate::report::{assert_eq, Test};
+use crate::suites::row_counts;
+use crate::tags::Tag;
+use color_eyre::{Report, Result};
+use demostf_client::ListParams;
+use reqwest::header::HeaderValue;
+use reqwest::StatusCode;
+
+fn payloads() -> Vec<String> {
+    let mut payloads: Vec<String> = [
+        "'; DROP TABLE demos; --",
+        "' OR '1'='1",
+        "\" OR \"\"=\"",
+        "1) UNION SELECT token FROM users --",
+        "%' OR 1=1 --",
+        "\\",
+        "../../../../etc/passwd",
+        "..\\..\\..\\windows\\win.ini",
+        "/etc/passwd",
+        "nul\0byte",
+        "%00",
+        "<script>alert(1)</script>",
+        "{{7*7}}${7*7}",
+        "\u{202e}gnp.exe",
+        "\u{feff}bom",
+        "e\u{301}\u{301}\u{301}",
+        "𝕿𝖊𝖘𝖙 🦀",
+        "",
+    ]
+    .iter()
+    .map(|payload| String::from(*payload))
+    .collect();
+    payloads.push("a".repeat(64 * 1024));
+    payloads
+}
+
+/// Values can be stored truncated but otherwise shouldn't be altered
+fn expect_stored(stored: &str, payloads: &[String]) -> Result<()> {
+    if payloads
+        .iter()
+        .any(|payload| payload == stored || (!stored.is_empty() && payload.starts_with(stored)))
+    {
+        Ok(())
+    } else {
+        Err(Report::msg(format!(
+            "Stored value {:?} doesn't match any of the inputs",
+            stored
+        )))
+    }
+}
+
+fn expect_handled(response: &RawResponse) -> Result<()> {
+    if response.status.is_server_error() {
+        return Err(Report::msg(format!(
+            "Unexpected server error {} with body {:?}",
+            response.status, response.body
+        )));
+    }
+    response.expect_no_leak()
+}
+
+async fn upload(raw: &RawClient) -> Result<()> {
+    raw.upload("test.dem", GRANARY, "RED", "BLUE", "token")
+        .await?
+        .expect_status(StatusCode::OK)
+}
+
+pub async fn run(harness: &Harness, edit_key: &str) -> bool {
+    let raw = &harness.raw();
+    let payloads = &payloads();
+    let mut success = true;
+
+    success &= Test::run(
+        "Security: upload names",
+        &[Tag::Upload, Tag::Slow],
+        harness,
+        |test| async move {
+            let accepted = test
+                .step("upload", |_| async move {
+                    let mut accepted: i64 = 0;
+                    for (index, payload) in payloads.iter().enumerate() {
+                        let response = raw
+                            .upload(
+                                payload,
+                                &fixtures::unique(GRANARY, &format!("security {}", index)),
+                                "RED",
+                                "BLUE",
+                                "token",
+                            )
+                            .await?;
+                        expect_handled(&response)?;
+                        if response.status == StatusCode::OK {
+                            accepted += 1;
+                        }
+                    }
+                    Ok(accepted)
+                })
+                .await?;
+
+            test.step("no extra demos", |_| async move {
+                assert_eq(harness.count_rows("demos").await?, accepted)
+            })
+            .await?;
+
+            test.step("urls contained", |client| async move {
+                for demo in client.list(ListParams::default(), 1).await? {
+                    if demo.url.contains("..")
+                        || demo.url.contains('\0')
+                        || demo.path.contains("..")
+                    {
+                        return Err(Report::msg(format!(
+                            "Demo {} stored outside of the demo root: {}",
+                            demo.id, demo.url
+                        )));
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Security: team names",
+        &[Tag::Upload, Tag::Slow],
+        harness,
+        |test| async move {
+            test.step("upload", |_| async move {
+                for (index, payload) in payloads.iter().enumerate() {
+                    let response = raw
+                        .upload(
+                            "test.dem",
+                            &fixtures::unique(GRANARY, &format!("security {}", index)),
+                            payload,
+                            payload,
+                            "token",
+                        )
+                        .await?;
+                    expect_handled(&response)?;
+                }
+                Ok(())
+            })
+            .await?;
+
+            test.step("stored as is", |client| async move {
+                for demo in client.list(ListParams::default(), 1).await? {
+                    expect_stored(&demo.red, payloads)?;
+                    expect_stored(&demo.blue, payloads)?;
+                }
+                Ok(())
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Security: map filter",
+        &[Tag::Listing, Tag::Slow],
+        harness,
+        |test| async move {
+            test.step("upload", |_| async move { upload(raw).await })
+                .await?;
+            let before = row_counts(harness).await?;
+
+            test.step("filter", |_| async move {
+                // an empty map filter is no filter at all
+                for payload in payloads.iter().filter(|payload| !payload.is_empty()) {
+                    let response = raw.get_query("demos", &[("map", payload)]).await?;
+                    expect_handled(&response)?;
+                    if response.status == StatusCode::OK {
+                        let demos = response.json()?;
+                        if demos.as_array().map(Vec::len).unwrap_or_default() > 0 {
+                            return Err(Report::msg(format!(
+                                "Map filter {:?} matched demos: {}",
+                                payload, demos
+                            )));
+                        }
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+
+            test.step("database unchanged", |_| async move {
+                assert_eq(row_counts(harness).await?, before)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Security: set url",
+        &[Tag::Auth, Tag::Slow],
+        harness,
+        |test| async move {
+            test.step("upload", |_| async move { upload(raw).await })
+                .await?;
+            let before = row_counts(harness).await?;
+            let hash = format!("{:x}", md5::compute(GRANARY));
+            let hash = hash.as_str();
+
+            test.step("set url", |_| async move {
+                for payload in payloads {
+                    let response = raw
+                        .post_form(
+                            "demos/1/url",
+                            &[
+                                ("hash", hash),
+                                ("backend", payload),
+                                ("url", "https://example.com/somedemo.dem"),
+                                ("path", payload),
+                                ("key", edit_key),
+                            ],
+                        )
+                        .await?;
+                    expect_handled(&response)?;
+                    if response.status == StatusCode::OK {
+                        let demo = raw.get("demos/1").await?.json()?;
+                        let backend = demo["backend"].as_str().unwrap_or_default();
+                        let path = demo["path"].as_str().unwrap_or_default();
+                        expect_stored(backend, payloads)?;
+                        expect_stored(path, payloads)?;
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+
+            test.step("database unchanged", |_| async move {
+                assert_eq(row_counts(harness).await?, before)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Security: access keys",
+        &[Tag::Auth, Tag::Private, Tag::Slow],
+        harness,
+        |test| async move {
+            test.step("upload private", |client| async move {
+                client
+                    .upload_private_demo(
+                        String::from("test.dem"),
+                        GRANARY.to_vec(),
+                        String::from("RED"),
+                        String::from("BLUE"),
+                        String::from("token"),
+                    )
+                    .await?;
+                Ok(())
+            })
+            .await?;
+            let before = row_counts(harness).await?;
+
+            test.step("get with access key", |_| async move {
+                for payload in payloads {
+                    // nul bytes and other control characters can't be sent in a header at all, so
+                    // there is no way for them to reach the api through the access key
+                    let value = match HeaderValue::from_bytes(payload.as_bytes()) {
+                        Ok(value) => value,
+                        Err(_) => continue,
+                    };
+                    let response = raw.get_with_header("demos/1", "ACCESS-KEY", value).await?;
+                    expect_handled(&response)?;
+                    if response.status == StatusCode::OK {
+                        let demo = response.json()?;
+                        if demo["url"] != "" || demo["backend"] != "" {
+                            return Err(Report::msg(format!(
+                                "Private demo storage exposed for access key {:?}: {}",
+                                payload, response.body
+                            )));
+                        }
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+
+            test.step("upload with key", |_| async move {
+                for (index, payload) in payloads.iter().enumerate() {
+                    let response = raw
+                        .upload(
+                            "test.dem",
+                            &fixtures::unique(GRANARY, &format!("security {}", index)),
+                            "RED",
+                            "BLUE",
+                            payload,
+                        )
+                        .await?;
+                    expect_handled(&response)?;
+                    if response.status == StatusCode::OK {
+                        return Err(Report::msg(format!(
+                            "Upload accepted with key {:?}",
+                            payload
+                        )));
+                    }
+                }
+                Ok(())
+            })
+            .await?;
+
+            test.step("database unchanged", |_| async move {
+                assert_eq(row_counts(harness).await?, before)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
@@ -0,0 +1,123 @@
+//! The demo file behind `demo.url` is stored under a hash prefixed path in the demo root and is
+//! byte for byte identical to the uploaded file.
+
+use crate::fixtures;
+use crate::harness::Harness;
+use crate::report::{assert_eq, Test};
+use crate::storage::{hex, storage_path, DemoStorage};
+use crate::tags::Tag;
+use color_eyre::{Report, Result};
+use colored::Colorize;
+use demostf_client::Demo;
+
+fn verify_layout(demo: &Demo, name: &str) -> Result<()> {
+    let path = storage_path(&demo.hash, name);
+    if !demo.path.ends_with(&path) {
+        return Err(Report::msg(format!(
+            "Expected stored path {} to end with {}",
+            demo.path, path
+        )));
+    }
+    if !demo.url.ends_with(&path) {
+        return Err(Report::msg(format!(
+            "Expected url {} to end with {}",
+            demo.url, path
+        )));
+    }
+    Ok(())
+}
+
+async fn verify_stored(storage: &DemoStorage, demo: &Demo, name: &str, data: &[u8]) -> Result<()> {
+    let stored = storage.read(&storage_path(&demo.hash, name)).await?;
+    assert_eq(stored.len(), data.len())?;
+    if stored != data {
+        return Err(Report::msg("Stored file doesn't match uploaded data"));
+    }
+    assert_eq(hex(&md5::compute(&stored).0), hex(&demo.hash))
+}
+
+pub async fn run(harness: &Harness) -> bool {
+    let storage = match DemoStorage::from_env() {
+        Some(storage) => storage,
+        None => {
+            println!(
+                " - Stored demo files: {}",
+                "skipped, DEMO_ROOT or STATIC_URL not set".yellow()
+            );
+            return true;
+        }
+    };
+    let storage = &storage;
+    let mut success = true;
+
+    success &= Test::run(
+        "Stored demo file",
+        &[Tag::Upload],
+        harness,
+        |test| async move {
+            let demo = test
+                .step("upload", |client| async move {
+                    let id = client
+                        .upload_demo(
+                            String::from("test.dem"),
+                            fixtures::GRANARY.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from("token"),
+                        )
+                        .await?;
+                    Ok(client.get(id).await?)
+                })
+                .await?;
+            let demo = &demo;
+
+            test.step("hash of upload", |_| async move {
+                assert_eq(hex(&md5::compute(fixtures::GRANARY).0), hex(&demo.hash))
+            })
+            .await?;
+
+            test.step("directory layout", |_| async move {
+                verify_layout(demo, "test.dem")
+            })
+            .await?;
+
+            test.step("stored file", |_| async move {
+                verify_stored(storage, demo, "test.dem", fixtures::GRANARY).await
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Stored files of all fixtures",
+        &[Tag::Upload],
+        harness,
+        |test| async move {
+            for (name, data) in fixtures::ALL {
+                test.step(name, |client| async move {
+                    let id = client
+                        .upload_demo(
+                            String::from(*name),
+                            data.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from("token"),
+                        )
+                        .await?;
+                    let demo = client.get(id).await?;
+                    verify_layout(&demo, name)?;
+                    verify_stored(storage, &demo, name, data).await
+                })
+                .await?;
+            }
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
@@ -0,0 +1,152 @@
+//! Storage keys allow external storage backends to move demos by setting their url, a key is only
+//! valid for the backend it was issued for and stops working once revoked or expired.
+
+use crate::fixtures::GRANARY;
+use crate::harness::Harness;
+use crate::report::Test;
+use crate::suites::upload_fixture;
+use crate::tags::Tag;
+use color_eyre::{Report, Result};
+use demostf_client::ApiClient;
+use time::{Duration, OffsetDateTime};
+
+const BACKEND: &str = "example";
+const KEY: &str = "storage_key";
+const URL: &str = "https://example.com/somedemo.dem";
+
+async fn set_url(client: &ApiClient, id: u32, backend: &str, key: &str) -> Result<()> {
+    let demo = client.get(id).await?;
+    client
+        .set_url(id, backend, "somedemo.dem", URL, demo.hash, key)
+        .await?;
+    Ok(())
+}
+
+/// The url of the demo should be left untouched when the key is rejected
+async fn expect_rejected(client: &ApiClient, id: u32, backend: &str, key: &str) -> Result<()> {
+    let demo = client.get(id).await?;
+    let result = client
+        .set_url(id, backend, "somedemo.dem", URL, demo.hash, key)
+        .await;
+
+    match result {
+        Ok(_) => return Err(Report::msg("Expected set url to be rejected")),
+        Err(demostf_client::Error::InvalidApiKey) => {}
+        Err(e) => return Err(Report::msg(format!("Unexpected error: {}", e))),
+    }
+
+    let demo = client.get(id).await?;
+    assert_object_eq!(demo => {
+        url == "https://localhost/ec/68/ec681d1b4846a7e3cb2c129fcbd858ac_test.dem",
+        backend == "static",
+    });
+    Ok(())
+}
+
+pub async fn run(harness: &Harness) -> bool {
+    let mut success = true;
+
+    success &= Test::run(
+        "Set url with storage key",
+        &[Tag::Auth, Tag::Destructive],
+        harness,
+        |test| async move {
+            harness.add_storage_key(BACKEND, KEY, None).await?;
+            let id = test
+                .step("upload", |client| async move {
+                    upload_fixture(client, "test.dem", GRANARY).await
+                })
+                .await?;
+
+            test.step("set url", |client| async move {
+                set_url(client, id, BACKEND, KEY).await?;
+                let demo = client.get(id).await?;
+                assert_object_eq!(demo => {
+                    url == URL,
+                    backend == BACKEND,
+                });
+                Ok(())
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Revoked storage key",
+        &[Tag::Auth, Tag::Destructive],
+        harness,
+        |test| async move {
+            harness.add_storage_key(BACKEND, KEY, None).await?;
+            let id = test
+                .step("upload", |client| async move {
+                    upload_fixture(client, "test.dem", GRANARY).await
+                })
+                .await?;
+
+            harness.revoke_storage_key(KEY).await?;
+
+            test.step("set url", |client| async move {
+                expect_rejected(client, id, BACKEND, KEY).await
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Expired storage key",
+        &[Tag::Auth, Tag::Destructive],
+        harness,
+        |test| async move {
+            let expired = OffsetDateTime::now_utc() - Duration::hours(1);
+            harness.add_storage_key(BACKEND, KEY, Some(expired)).await?;
+            let id = test
+                .step("upload", |client| async move {
+                    upload_fixture(client, "test.dem", GRANARY).await
+                })
+                .await?;
+
+            test.step("set url", |client| async move {
+                expect_rejected(client, id, BACKEND, KEY).await
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Storage key for other backend",
+        &[Tag::Auth, Tag::Destructive],
+        harness,
+        |test| async move {
+            harness.add_storage_key("other", KEY, None).await?;
+            let id = test
+                .step("upload", |client| async move {
+                    upload_fixture(client, "test.dem", GRANARY).await
+                })
+                .await?;
+
+            test.step("set url", |client| async move {
+                expect_rejected(client, id, BACKEND, KEY).await
+            })
+            .await?;
+
+            test.step("set url for issued backend", |client| async move {
+                set_url(client, id, "other", KEY).await
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
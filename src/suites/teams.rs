@@ -0,0 +1,221 @@
+//! Team names given on upload are stored as is and returned with the demo, both when getting a
+//! single demo and in listings.
+//!
+//! Names recorded in the `teams` table are stored once, uploading the same demo again or another
+//! demo with the same team names doesn't add rows.
+
+use crate::fixtures;
+use crate::harness::Harness;
+use crate::report::{assert_eq, is_server_error, Test};
+use crate::suites::upload_with_teams;
+use crate::tags::Tag;
+use color_eyre::{Report, Result};
+use demostf_client::{ApiClient, ListOrder, ListParams};
+
+const UNICODE_RED: &str = "Ünïcødé 🦀";
+const UNICODE_BLUE: &str = "チーム ブルー";
+
+async fn verify_teams(client: &ApiClient, id: u32, red: &str, blue: &str) -> Result<()> {
+    let demo = client.get(id).await?;
+    assert_object_eq!(demo => {
+        red == red,
+        blue == blue,
+    });
+
+    let list = client
+        .list(ListParams::default().with_order(ListOrder::Ascending), 1)
+        .await?;
+    let listed = list
+        .iter()
+        .find(|demo| demo.id == id)
+        .ok_or_else(|| Report::msg(format!("Demo {} not listed", id)))?;
+    assert_object_eq!(listed => {
+        red == red,
+        blue == blue,
+    });
+    Ok(())
+}
+
+/// Team names that exceed the storage size can be rejected or truncated but shouldn't cause an error
+fn verify_truncated(stored: &str, sent: &str) -> Result<()> {
+    if sent.starts_with(stored) {
+        Ok(())
+    } else {
+        Err(Report::msg(format!(
+            "Stored team name {:?} is not a prefix of {:?}",
+            stored, sent
+        )))
+    }
+}
+
+/// Rows added to the teams table since `before`, which should only be the given names, once each
+fn expect_new_teams(before: &[String], after: &[String], names: &[&str]) -> Result<()> {
+    let added = after.get(before.len()..).unwrap_or_default();
+    assert_eq(&after[..before.len().min(after.len())], before)?;
+    for (i, name) in added.iter().enumerate() {
+        if !names.contains(&name.as_str()) || added[..i].contains(name) {
+            return Err(Report::msg(format!(
+                "Unexpected team {:?} stored, expected each of {:?} at most once",
+                name, names
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub async fn run(harness: &Harness) -> bool {
+    let mut success = true;
+
+    success &= Test::run(
+        "Team names",
+        &[Tag::Upload, Tag::Listing],
+        harness,
+        |test| async move {
+            test.step("custom names", |client| async move {
+                let id = upload_with_teams(
+                    client,
+                    "test.dem",
+                    fixtures::GRANARY,
+                    "Team Foo",
+                    "Team Bar",
+                )
+                .await?;
+                verify_teams(client, id, "Team Foo", "Team Bar").await
+            })
+            .await?;
+
+            test.step("empty names", |client| async move {
+                let id = upload_with_teams(client, "test.dem", fixtures::PROCESS, "", "").await?;
+                verify_teams(client, id, "", "").await
+            })
+            .await?;
+
+            test.step("unicode names", |client| async move {
+                let id = upload_with_teams(
+                    client,
+                    "test.dem",
+                    fixtures::RECONNECT,
+                    UNICODE_RED,
+                    UNICODE_BLUE,
+                )
+                .await?;
+                verify_teams(client, id, UNICODE_RED, UNICODE_BLUE).await
+            })
+            .await?;
+
+            test.step("very long names", |client| async move {
+                let red = "R".repeat(1024);
+                let blue = "B".repeat(1024);
+                let result = client
+                    .upload_demo(
+                        String::from("test.dem"),
+                        fixtures::WARMFROST.to_vec(),
+                        red.clone(),
+                        blue.clone(),
+                        String::from("token"),
+                    )
+                    .await;
+                match result {
+                    Ok(id) => {
+                        let demo = client.get(id).await?;
+                        verify_truncated(&demo.red, &red)?;
+                        verify_truncated(&demo.blue, &blue)
+                    }
+                    Err(e) if is_server_error(&e) => Err(Report::msg(format!(
+                        "Unexpected server error during upload: {}",
+                        e
+                    ))),
+                    Err(_) => Ok(()),
+                }
+            })
+            .await?;
+
+            test.step("repeated names", |client| async move {
+                let id = upload_with_teams(
+                    client,
+                    "test.dem",
+                    fixtures::ULTIDUO,
+                    "Team Foo",
+                    "Team Bar",
+                )
+                .await?;
+                verify_teams(client, id, "Team Foo", "Team Bar").await
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Teams table",
+        &[Tag::Upload, Tag::Destructive],
+        harness,
+        |test| async move {
+            let before = harness.team_names().await?;
+            let before = &before;
+
+            let after = test
+                .step("upload", |client| async move {
+                    upload_with_teams(
+                        client,
+                        "test.dem",
+                        fixtures::GRANARY,
+                        "Team Foo",
+                        "Team Bar",
+                    )
+                    .await?;
+                    let after = harness.team_names().await?;
+                    expect_new_teams(before, &after, &["Team Foo", "Team Bar"])?;
+                    Ok(after)
+                })
+                .await?;
+            let after = &after;
+
+            test.step("re-upload", |client| async move {
+                upload_with_teams(
+                    client,
+                    "test.dem",
+                    fixtures::GRANARY,
+                    "Team Foo",
+                    "Team Bar",
+                )
+                .await?;
+                assert_eq(&harness.team_names().await?, after)
+            })
+            .await?;
+
+            test.step("other demo with the same names", |client| async move {
+                upload_with_teams(
+                    client,
+                    "test.dem",
+                    fixtures::PROCESS,
+                    "Team Foo",
+                    "Team Bar",
+                )
+                .await?;
+                assert_eq(&harness.team_names().await?, after)
+            })
+            .await?;
+
+            test.step("other demo with new names", |client| async move {
+                upload_with_teams(
+                    client,
+                    "test.dem",
+                    fixtures::ULTIDUO,
+                    "Team Baz",
+                    "Team Bar",
+                )
+                .await?;
+                expect_new_teams(after, &harness.team_names().await?, &["Team Baz"])
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
@@ -0,0 +1,252 @@
+//! Timing attack and brute force resistance of the edit and access keys
+//!
+//! Keys that share a prefix with the correct key shouldn't be rejected measurably slower than keys
+//! that differ from the first character, as that would allow recovering the key one character at
+//! a time. When the api advertises a rate limit on the key protected endpoints, repeated wrong keys
+//! should be rejected once the limit is hit.
+//!
+//! These checks send a lot of requests and are sensitive to noise, so they only run when
+//! `TIMING_CHECKS` is set.
+
+use crate::env::env_or;
+use crate::fixtures::GRANARY;
+use crate::harness::Harness;
+use crate::report::Test;
+use crate::tags::Tag;
+use color_eyre::{eyre::WrapErr, Report, Result};
+use colored::Colorize;
+use demostf_client::ApiClient;
+use reqwest::StatusCode;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Measuring the response times takes a lot of requests, so it gets more time than a normal step
+const MEASURE_TIMEOUT: Duration = Duration::from_secs(600);
+
+struct TimingConfig {
+    /// Number of requests for each key variant
+    samples: usize,
+    /// Welch's t statistic above which the difference is considered significant
+    threshold: f64,
+}
+
+impl TimingConfig {
+    fn from_env() -> Result<Option<Self>> {
+        if dotenv::var("TIMING_CHECKS").is_err() {
+            return Ok(None);
+        }
+        let samples = env_or("TIMING_SAMPLES", 200)?;
+        let threshold = env_or("TIMING_THRESHOLD", 4.0)?;
+        Ok(Some(TimingConfig { samples, threshold }))
+    }
+}
+
+/// The key with the character at `position` replaced
+fn variant(key: &str, position: usize) -> String {
+    key.chars()
+        .enumerate()
+        .map(|(index, c)| match (index == position, c) {
+            (true, 'x') => 'y',
+            (true, _) => 'x',
+            (false, c) => c,
+        })
+        .collect()
+}
+
+/// Drop the slowest 10% of the samples, which are mostly noise from the network or scheduler
+fn trimmed(mut samples: Vec<f64>) -> Vec<f64> {
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    samples.truncate(samples.len() - samples.len() / 10);
+    samples
+}
+
+fn mean_variance(samples: &[f64]) -> (f64, f64) {
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let variance =
+        samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (samples.len() - 1) as f64;
+    (mean, variance)
+}
+
+/// Welch's t statistic for the difference in means of two samples
+fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+    let (mean_a, variance_a) = mean_variance(a);
+    let (mean_b, variance_b) = mean_variance(b);
+    let error = (variance_a / a.len() as f64 + variance_b / b.len() as f64).sqrt();
+    if error == 0.0 {
+        0.0
+    } else {
+        (mean_a - mean_b) / error
+    }
+}
+
+/// Measure the request with both keys, interleaved so drift in the api affects both equally
+async fn compare<F, Fut>(config: &TimingConfig, key: &str, request: F) -> Result<()>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let close = variant(key, key.chars().count().saturating_sub(1));
+    let far = variant(key, 0);
+    let mut close_samples = Vec::with_capacity(config.samples);
+    let mut far_samples = Vec::with_capacity(config.samples);
+
+    for _ in 0..config.samples {
+        for (key, samples) in [(&close, &mut close_samples), (&far, &mut far_samples)] {
+            let start = Instant::now();
+            request(key.clone()).await?;
+            samples.push(start.elapsed().as_secs_f64() * 1000.0);
+        }
+    }
+
+    let close_samples = trimmed(close_samples);
+    let far_samples = trimmed(far_samples);
+    let t = welch_t(&close_samples, &far_samples);
+    if t.abs() > config.threshold {
+        Err(Report::msg(format!(
+            "Response time depends on the key prefix: {:.2}ms for a matching prefix, {:.2}ms otherwise (t = {:.1})",
+            mean_variance(&close_samples).0,
+            mean_variance(&far_samples).0,
+            t
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+async fn expect_rejected_set_url(client: &ApiClient, id: u32, key: &str) -> Result<()> {
+    match client
+        .set_url(
+            id,
+            "example",
+            "somedemo.dem",
+            "https://example.com/somedemo.dem",
+            md5::compute(GRANARY).0,
+            key,
+        )
+        .await
+    {
+        Err(demostf_client::Error::InvalidApiKey) => Ok(()),
+        Ok(_) => Err(Report::msg("Expected the key to be rejected")),
+        Err(e) => Err(Report::msg(format!("Unexpected error: {}", e))),
+    }
+}
+
+pub async fn run(harness: &Harness, edit_key: &str, access_key: &str) -> bool {
+    let config = match TimingConfig::from_env() {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            println!(
+                " - Key timing: {}",
+                "skipped, TIMING_CHECKS not set".yellow()
+            );
+            return true;
+        }
+        Err(e) => {
+            println!(" - Key timing: {:#}", e);
+            return false;
+        }
+    };
+    let config = &config;
+    let raw = &harness.raw();
+    let mut success = true;
+
+    success &= Test::run(
+        "Key timing: set url",
+        &[Tag::Auth, Tag::Slow],
+        harness,
+        |test| async move {
+            let id = test
+                .step("upload", |client| async move {
+                    Ok(client
+                        .upload_demo(
+                            String::from("test.dem"),
+                            GRANARY.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from("token"),
+                        )
+                        .await?)
+                })
+                .await?;
+            let hash = format!("{:x}", md5::compute(GRANARY));
+            let hash = hash.as_str();
+
+            test.step_with_timeout("edit key prefix", MEASURE_TIMEOUT, |client| async move {
+                compare(config, edit_key, |key| async move {
+                    expect_rejected_set_url(client, id, &key).await
+                })
+                .await
+            })
+            .await?;
+
+            test.step("rate limit", |_| async move {
+                let form = [
+                    ("hash", hash),
+                    ("backend", "example"),
+                    ("url", "https://example.com/somedemo.dem"),
+                    ("path", "somedemo.dem"),
+                    ("key", "wrong"),
+                ];
+                let response = raw.post_form("demos/1/url", &form).await?;
+                let limit = match response.header("x-ratelimit-limit") {
+                    Ok(limit) => limit.parse::<u64>().wrap_err("Invalid X-RateLimit-Limit")?,
+                    Err(_) => {
+                        println!("      {}", "no rate limit advertised".yellow());
+                        return Ok(());
+                    }
+                };
+                for _ in 0..limit {
+                    let response = raw.post_form("demos/1/url", &form).await?;
+                    if response.status == StatusCode::TOO_MANY_REQUESTS {
+                        return Ok(());
+                    }
+                }
+                Err(Report::msg(format!(
+                    "Advertised rate limit of {} not enforced",
+                    limit
+                )))
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Key timing: private access",
+        &[Tag::Auth, Tag::Private, Tag::Slow],
+        harness,
+        |test| async move {
+            let id = test
+                .step("upload private", |client| async move {
+                    Ok(client
+                        .upload_private_demo(
+                            String::from("test.dem"),
+                            GRANARY.to_vec(),
+                            String::from("RED"),
+                            String::from("BLUE"),
+                            String::from("token"),
+                        )
+                        .await?)
+                })
+                .await?;
+
+            test.step_with_timeout("access key prefix", MEASURE_TIMEOUT, |client| async move {
+                compare(config, access_key, |key| async move {
+                    let mut client = client.clone();
+                    client.set_access_key(key);
+                    client.get(id).await?;
+                    Ok(())
+                })
+                .await
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
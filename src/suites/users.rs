@@ -0,0 +1,284 @@
+//! User lookup by id and steam id, and player search
+//!
+//! Users are created for every player in an uploaded demo. A user keeps the name they were first
+//! seen with, while the players of each demo keep the name used in that demo.
+
+use crate::fixtures::{GRANARY, RECONNECT};
+use crate::harness::Harness;
+use crate::raw::RawClient;
+use crate::report::{assert_eq, Test};
+use crate::schema::Schema;
+use crate::suites::upload_fixture;
+use crate::tags::Tag;
+use color_eyre::{Report, Result};
+use demostf_client::{ApiClient, Error, Player};
+use reqwest::StatusCode;
+use serde_json::Value;
+
+const UPLOADER: u64 = 76561198024494988;
+const UNKNOWN_PLAYER: u64 = 76561197992320000;
+/// Player that appears in both the granary and reconnect fixtures
+const SHARED_PLAYER: u64 = 76561197992327511;
+
+/// Upload the granary fixture, returning the id of the uploader and the players in the demo
+async fn upload(client: &ApiClient) -> Result<(u32, Vec<Player>)> {
+    let id = upload_fixture(client, "test.dem", GRANARY).await?;
+    let demo = client.get(id).await?;
+    let players = demo.get_players(client).await?.into_owned();
+    Ok((demo.uploader.id(), players))
+}
+
+/// The typed client only looks users up by id, the api also accepts a steam id
+async fn get_by_steam_id(raw: &RawClient, schema: &Schema, steam_id: u64) -> Result<Value> {
+    let response = raw.get(&format!("users/{}", steam_id)).await?;
+    response.expect_status(StatusCode::OK)?;
+    let user = response.json()?;
+    schema.validate("user", &user)?;
+    Ok(user)
+}
+
+/// Steam ids of the users found by the search
+async fn search(client: &ApiClient, query: &str) -> Result<Vec<u64>> {
+    Ok(client
+        .search_users(query)
+        .await?
+        .iter()
+        .map(|user| u64::from(user.steam_id))
+        .collect())
+}
+
+fn expect_found(results: &[u64], steam_id: u64, query: &str) -> Result<()> {
+    if results.contains(&steam_id) {
+        Ok(())
+    } else {
+        Err(Report::msg(format!(
+            "Expected search for {:?} to find {}, got {:?}",
+            query, steam_id, results
+        )))
+    }
+}
+
+pub async fn run(harness: &Harness) -> bool {
+    let raw = &harness.raw();
+    let schema = match Schema::load() {
+        Ok(schema) => schema,
+        Err(e) => {
+            println!(" - Users: failed to load schema: {:#}", e);
+            return false;
+        }
+    };
+    let schema = &schema;
+    let mut success = true;
+
+    success &= Test::run("User by id", &[Tag::Upload], harness, |test| async move {
+        let (uploader, players) = test
+            .step("upload", |client| async move { upload(client).await })
+            .await?;
+        let players = &players;
+
+        test.step("uploader", |client| async move {
+            let user = client.get_user(uploader).await?;
+            assert_eq(user.name.as_str(), "Icewind")
+        })
+        .await?;
+
+        test.step("players", |client| async move {
+            for player in players.iter() {
+                let user = client.get_user(player.user.id).await?;
+                assert_eq(u64::from(user.steam_id), u64::from(player.user.steam_id))?;
+                assert_eq(user.name.as_str(), player.user.name.as_str())?;
+            }
+            Ok(())
+        })
+        .await?;
+
+        test.step("unknown id", |client| async move {
+            match client.get_user(999_999).await {
+                Err(Error::UserNotFound(999_999)) => Ok(()),
+                Err(e) => Err(Report::msg(format!("Expected user not found, got {}", e))),
+                Ok(user) => Err(Report::msg(format!(
+                    "Expected user not found, got {:?}",
+                    user
+                ))),
+            }
+        })
+        .await?;
+
+        test.step("invalid id", |_| async move {
+            let response = raw.get("users/not-a-user-id").await?;
+            if response.status.is_client_error() {
+                Ok(())
+            } else {
+                Err(Report::msg(format!(
+                    "Expected client error, got {}",
+                    response.status
+                )))
+            }
+        })
+        .await?;
+
+        Ok(())
+    })
+    .await;
+
+    success &= Test::run(
+        "User by steam id",
+        &[Tag::Upload],
+        harness,
+        |test| async move {
+            let (_, players) = test
+                .step("upload", |client| async move { upload(client).await })
+                .await?;
+            let players = &players;
+
+            test.step("uploader", |_| async move {
+                let user = get_by_steam_id(raw, schema, UPLOADER).await?;
+                assert_eq(user["name"].as_str(), Some("Icewind"))
+            })
+            .await?;
+
+            test.step("players", |_| async move {
+                for player in players.iter() {
+                    let steam_id = u64::from(player.user.steam_id);
+                    let user = get_by_steam_id(raw, schema, steam_id).await?;
+                    assert_eq(user["id"].as_u64(), Some(player.user.id as u64))?;
+                    assert_eq(user["name"].as_str(), Some(player.user.name.as_str()))?;
+                }
+                Ok(())
+            })
+            .await?;
+
+            test.step("unknown steam id", |_| async move {
+                raw.get(&format!("users/{}", UNKNOWN_PLAYER))
+                    .await?
+                    .expect_status(StatusCode::NOT_FOUND)
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Player search",
+        &[Tag::Upload],
+        harness,
+        |test| async move {
+            let (_, players) = test
+                .step("upload", |client| async move { upload(client).await })
+                .await?;
+            let players = &players;
+
+            test.step("full name", |client| async move {
+                for player in players.iter() {
+                    let name = player.user.name.as_str();
+                    expect_found(
+                        &search(client, name).await?,
+                        player.user.steam_id.into(),
+                        name,
+                    )?;
+                }
+                Ok(())
+            })
+            .await?;
+
+            test.step("partial name", |client| async move {
+                for player in players.iter() {
+                    let name: String = player.user.name.chars().take(4).collect();
+                    if name.chars().count() < 4 {
+                        continue;
+                    }
+                    expect_found(
+                        &search(client, &name).await?,
+                        player.user.steam_id.into(),
+                        &name,
+                    )?;
+                }
+                Ok(())
+            })
+            .await?;
+
+            test.step("no match", |client| async move {
+                assert_eq(search(client, "zzzzzzzzzzzzzzzz").await?.len(), 0)
+            })
+            .await?;
+
+            test.step("empty query", |_| async move {
+                let response = raw.get_query("users/search", &[("query", "")]).await?;
+                if response.status.is_server_error() {
+                    return Err(Report::msg(format!(
+                        "Unexpected server error {}",
+                        response.status
+                    )));
+                }
+                Ok(())
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success &= Test::run(
+        "Renamed player",
+        &[Tag::Upload, Tag::Destructive],
+        harness,
+        |test| async move {
+            test.step("upload first demo", |client| async move {
+                upload(client).await?;
+                Ok(())
+            })
+            .await?;
+
+            // pretend the player used a different name when the first demo was recorded
+            harness.set_user_name(SHARED_PLAYER, "Old Name").await?;
+
+            let player = test
+                .step("upload second demo", |client| async move {
+                    let id = upload_fixture(client, "second.dem", RECONNECT).await?;
+                    let players = client
+                        .get(id)
+                        .await?
+                        .get_players(client)
+                        .await?
+                        .into_owned();
+                    players
+                        .into_iter()
+                        .find(|player| u64::from(player.user.steam_id) == SHARED_PLAYER)
+                        .ok_or_else(|| Report::msg("Player not found in second demo"))
+                })
+                .await?;
+            let player = &player;
+
+            test.step("user keeps name", |client| async move {
+                let user = client.get_user(player.user.id).await?;
+                assert_eq(user.name.as_str(), "Old Name")
+            })
+            .await?;
+
+            test.step("player has name from demo", |_| async move {
+                if player.user.name == "Old Name" {
+                    return Err(Report::msg("Player name was overwritten by the user name"));
+                }
+                Ok(())
+            })
+            .await?;
+
+            test.step("search by old name", |client| async move {
+                expect_found(
+                    &search(client, "Old Name").await?,
+                    SHARED_PLAYER,
+                    "Old Name",
+                )
+            })
+            .await?;
+
+            Ok(())
+        },
+    )
+    .await;
+
+    success
+}
@@ -0,0 +1,73 @@
+//! Tags for selecting which tests to run
+//!
+//! Every test is tagged with the areas it covers, the command line options `--tags` and
+//! `--exclude-tags` take a comma separated list of tags to include or exclude. `--suite smoke`
+//! excludes the slow tests for a quick check.
+
+use color_eyre::{Report, Result};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tag {
+    Upload,
+    Listing,
+    Auth,
+    Private,
+    /// Tests that take significantly longer than the rest
+    Slow,
+    /// Tests that modify the database directly instead of only through the api
+    Destructive,
+}
+
+impl FromStr for Tag {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "upload" => Ok(Tag::Upload),
+            "listing" => Ok(Tag::Listing),
+            "auth" => Ok(Tag::Auth),
+            "private" => Ok(Tag::Private),
+            "slow" => Ok(Tag::Slow),
+            "destructive" => Ok(Tag::Destructive),
+            _ => Err(Report::msg(format!("Unknown tag {}", s))),
+        }
+    }
+}
+
+fn parse_tags(tags: Option<&str>) -> Result<Vec<Tag>> {
+    tags.into_iter()
+        .flat_map(|tags| tags.split(','))
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| tag.trim().parse())
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub struct TagFilter {
+    /// Only run tests with any of these tags, runs all tests if empty
+    include: Vec<Tag>,
+    exclude: Vec<Tag>,
+}
+
+impl TagFilter {
+    pub fn parse(
+        include: Option<&str>,
+        exclude: Option<&str>,
+        suite: Option<&str>,
+    ) -> Result<Self> {
+        let include = parse_tags(include)?;
+        let mut exclude = parse_tags(exclude)?;
+        match suite {
+            None | Some("full") => {}
+            Some("smoke") => exclude.push(Tag::Slow),
+            Some(suite) => return Err(Report::msg(format!("Unknown suite {}", suite))),
+        }
+        Ok(TagFilter { include, exclude })
+    }
+
+    pub fn matches(&self, tags: &[Tag]) -> bool {
+        (self.include.is_empty() || tags.iter().any(|tag| self.include.contains(tag)))
+            && !tags.iter().any(|tag| self.exclude.contains(tag))
+    }
+}
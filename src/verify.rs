@@ -0,0 +1,153 @@
+use crate::report::assert_eq;
+use color_eyre::{eyre::WrapErr, Report, Result};
+use demostf_client::{ChatMessage, Class, Demo, SteamID, Team};
+use std::convert::TryFrom;
+use std::panic::catch_unwind;
+use tf_demo_parser::{demo::header::Header, DemoParser, MatchState};
+
+/// Parse a demo file, treating a panic in the parser as a parse error
+pub fn parse_demo(data: &[u8]) -> Result<(Header, MatchState)> {
+    catch_unwind(|| {
+        let demo = tf_demo_parser::Demo::new(data);
+        let parser = DemoParser::new(demo.get_stream());
+        parser.parse()
+    })
+    .map_err(|_| Report::msg("Parser panicked"))?
+    .map_err(|_| Report::msg("Failed to parse demo"))
+}
+
+pub fn verify_demo(api_result: &Demo, header: &Header, state: &MatchState) -> Result<()> {
+    use tf_demo_parser::demo::parser::gamestateanalyser;
+
+    fn map_team(team: Team) -> gamestateanalyser::Team {
+        match team {
+            Team::Red => gamestateanalyser::Team::Red,
+            Team::Blue => gamestateanalyser::Team::Blue,
+        }
+    }
+
+    fn map_class(class: Class) -> gamestateanalyser::Class {
+        match class {
+            Class::Scout => gamestateanalyser::Class::Scout,
+            Class::Soldier => gamestateanalyser::Class::Soldier,
+            Class::Pyro => gamestateanalyser::Class::Pyro,
+            Class::Demoman => gamestateanalyser::Class::Demoman,
+            Class::HeavyWeapons => gamestateanalyser::Class::Heavy,
+            Class::Medic => gamestateanalyser::Class::Medic,
+            Class::Engineer => gamestateanalyser::Class::Engineer,
+            Class::Sniper => gamestateanalyser::Class::Sniper,
+            Class::Spy => gamestateanalyser::Class::Spy,
+        }
+    }
+
+    assert_eq(&api_result.map, &header.map).wrap_err("Failed to compare map")?;
+    assert_eq(
+        api_result.red_score,
+        state
+            .rounds
+            .iter()
+            .filter(|round| round.winner == gamestateanalyser::Team::Red)
+            .count() as u8,
+    )
+    .wrap_err("Failed to compare red score")?;
+    assert_eq(
+        api_result.blue_score,
+        state
+            .rounds
+            .iter()
+            .filter(|round| round.winner == gamestateanalyser::Team::Blue)
+            .count() as u8,
+    )
+    .wrap_err("Failed to compare blue score")?;
+    assert_eq(&api_result.server, &header.server).wrap_err("Failed to compare server")?;
+    assert_eq(&api_result.nick, &header.nick).wrap_err("Failed to compare server")?;
+    assert_eq(api_result.duration, header.duration as u16).wrap_err("Failed to compare server")?;
+
+    let mut players = state
+        .users
+        .values()
+        .filter(|user| user.team.is_player())
+        .map(|user| {
+            SteamID::try_from(user.steam_id.as_str())
+                .map(|steam_id| (steam_id, user))
+                .map_err(|_| Report::msg(format!("Invalid steam id {} in demo", user.steam_id)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    players.sort_by_key(|(steam_id, _)| steam_id.account_id());
+
+    let mut api_players = api_result
+        .players
+        .clone()
+        .ok_or_else(|| Report::msg("Demo has no player list"))?;
+    api_players.sort_by(|a, b| {
+        a.user
+            .steam_id
+            .account_id()
+            .cmp(&b.user.steam_id.account_id())
+    });
+
+    assert_eq(api_result.player_count, players.len() as u8)
+        .wrap_err("Failed to compare player count")?;
+    assert_eq(api_players.len(), players.len()).wrap_err("Failed to compare player count")?;
+
+    for (api_player, (steam_id, player)) in api_players.iter().zip(players.iter()) {
+        assert_eq(&api_player.user.name, &player.name).wrap_err_with(|| {
+            format!("Failed to compare player name for {}", api_player.user.name)
+        })?;
+        assert_eq(&api_player.user.steam_id, steam_id)
+            .wrap_err_with(|| format!("Failed to compare steam id for {}", api_player.user.name))?;
+        assert_eq(map_team(api_player.team), player.team)
+            .wrap_err_with(|| format!("Failed to compare team for {}", api_player.user.name))?;
+        let class = player
+            .classes
+            .sorted()
+            .next()
+            .ok_or_else(|| Report::msg(format!("No class for {}", api_player.user.name)))?
+            .0;
+        assert_eq(map_class(api_player.class), class)
+            .wrap_err_with(|| format!("Failed to compare class for {}", api_player.user.name))?;
+        let kills = state
+            .deaths
+            .iter()
+            .filter(|kill| kill.killer == player.user_id)
+            .count() as u8;
+        let assists = state
+            .deaths
+            .iter()
+            .filter(|kill| kill.assister == Some(player.user_id))
+            .count() as u8;
+        let deaths = state
+            .deaths
+            .iter()
+            .filter(|kill| kill.victim == player.user_id)
+            .count() as u8;
+        assert_eq(api_player.kills, kills)
+            .wrap_err_with(|| format!("Failed to compare kills for {}", api_player.user.name))?;
+        assert_eq(api_player.assists, assists)
+            .wrap_err_with(|| format!("Failed to compare assists for {}", api_player.user.name))?;
+        assert_eq(api_player.deaths, deaths)
+            .wrap_err_with(|| format!("Failed to compare deaths for {}", api_player.user.name))?;
+    }
+
+    Ok(())
+}
+
+pub fn verify_chat(chat: &[ChatMessage], state: &MatchState) -> Result<()> {
+    assert_eq(chat.len(), state.chat.len())
+        .wrap_err("Failed to compare number of chat messages")?;
+
+    let mut demo_chat = state.chat.clone();
+    demo_chat.sort_by_key(|chat| chat.tick);
+
+    for (api_chat, chat) in chat.iter().zip(demo_chat.iter()) {
+        assert_eq(&api_chat.message, &chat.text).wrap_err("Failed to compare chat message")?;
+        assert_eq(&api_chat.user, &chat.from).wrap_err("Failed to compare chat message sender")?;
+        // assert_eq(
+        //     api_chat.time,
+        //     (chat.tick as f32 * state.interval_per_tick) as u32,
+        // )
+        // .wrap_err("Failed to compare chat message time")?;
+    }
+
+    Ok(())
+}